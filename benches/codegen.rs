@@ -0,0 +1,25 @@
+// Compare the interpreter with the code generated by 'intcode::codegen::generate', run with 'cargo bench'.
+
+use std::time::Instant;
+
+use advent_of_code_2019::intcode::{self, codegen};
+
+include!("../src/intcode/codegen/sum_to_n.rs");
+
+fn main() {
+    let n = 1_000_000;
+
+    let now = Instant::now();
+    let mut buffer = intcode::Buffer::from(&[n]);
+    intcode::execute_op_code_with_custom_io(&codegen::SUM_TO_N, &mut buffer).unwrap();
+    let interpreted = now.elapsed();
+    let interpreted_output = buffer.output;
+
+    let now = Instant::now();
+    let mut buffer = intcode::Buffer::from(&[n]);
+    sum_to_n(&mut buffer).unwrap();
+    let generated = now.elapsed();
+
+    assert_eq!(buffer.output, interpreted_output);
+    println!("Sum to {}: interpreted: {} μs, generated: {} μs", n, interpreted.as_micros(), generated.as_micros());
+}
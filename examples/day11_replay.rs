@@ -0,0 +1,46 @@
+// Watch the robot of day 11 paint the hull, in the terminal or as PPM images.
+// Usage: cargo run --example day11_replay -- <program file> [--white] [--steps <n>] [--fps <n>] [--ppm <directory>] [--cell <pixels>]
+// Example: cargo run --example day11_replay -- data/day11.input --white --steps 5 --fps 30
+
+use std::{env, io, time::Duration};
+
+use advent_of_code_2019::{common, day11::{self, replay::Replay}};
+
+const USAGE: &str = "Usage: day11_replay <program file> [--white] [--steps <n>] [--fps <n>] [--ppm <directory>] [--cell <pixels>]";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let option = |name: &str| args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1));
+    let number = |name: &str, default: usize| option(name).map_or(default, |value| value.parse::<usize>().expect(USAGE));
+
+    let file = match args.first() {
+        Some(file) if !file.starts_with("--") => file,
+        _ => {
+            println!("{}", USAGE);
+            return
+        }
+    };
+
+    let code = common::read_list_of_numbers::<&str, i64>(file, ",");
+    let mut hull = day11::Hull::new();
+    if args.iter().any(|arg| arg == "--white") {
+        hull.panels.insert((0, 0), 1);
+    }
+
+    let robot = day11::paint(&code, day11::Robot::new(day11::Palette::black_and_white(), day11::Steering::LeftRight, hull.clone())).unwrap();
+    let replay = Replay::new(&hull, &robot);
+    let steps_per_frame = number("--steps", 1);
+
+    match option("--ppm") {
+        Some(directory) => {
+            let nb_frames = replay.export_ppm(directory, steps_per_frame, number("--cell", 8)).unwrap();
+            println!("{} frames written in {}", nb_frames, directory);
+        },
+        None => {
+            let delay = Duration::from_secs_f64(1.0 / number("--fps", 20).max(1) as f64);
+            replay.play(&mut io::stdout(), steps_per_frame, delay).unwrap();
+        }
+    }
+
+    println!("{}", replay.summary());
+}
@@ -0,0 +1,91 @@
+// Play the breakout game of day 13 in the terminal.
+// Keys: 'a' or left arrow: left, 's': neutral, 'd' or right arrow: right, 'p': autopilot on/off, 'q': quit.
+// A script uses the same keys, one per frame, and '.' for no key.
+// Usage: cargo run --example day13_arcade -- <program file> [--fps <n>] [--autopilot] [--script <keys>]
+// Example: cargo run --example day13_arcade -- data/day13.input --fps 15
+
+use std::{env, io::{self, Read}, process::Command, sync::mpsc, thread, time::Duration};
+
+use advent_of_code_2019::{common, day13::play::{self, Key, Player, Script}};
+
+const USAGE: &str = "Usage: day13_arcade <program file> [--fps <n>] [--autopilot] [--script <keys>]";
+
+// Read the keys without waiting for a new line and without echo until dropped, even if the game panics.
+struct RawTerminal;
+
+impl RawTerminal {
+    fn new() -> Self {
+        let _ = Command::new("stty").args(["-icanon", "-echo"]).status();
+        RawTerminal
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = Command::new("stty").args(["icanon", "echo"]).status();
+    }
+}
+
+// Send the keys typed in the terminal, the arrows are sent as "ESC [ D" and "ESC [ C".
+fn read_keys(sender: mpsc::Sender<Key>) {
+    let mut previous = [0u8; 2];
+    for byte in io::stdin().lock().bytes() {
+        let byte = match byte {
+            Ok(byte) => byte,
+            Err(_) => return
+        };
+        let key =
+            match (previous, byte) {
+                ([0x1b, b'['], b'D') => Some(Key::Left),
+                ([0x1b, b'['], b'C') => Some(Key::Right),
+                _ => Key::from_char(byte as char)
+            };
+        previous = [previous[1], byte];
+        if let Some(key) = key {
+            if sender.send(key).is_err() || key == Key::Quit {
+                return
+            }
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let option = |name: &str| args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1));
+
+    let file = match args.first() {
+        Some(file) if !file.starts_with("--") => file,
+        _ => {
+            println!("{}", USAGE);
+            return
+        }
+    };
+
+    let code = common::read_list_of_numbers::<&str, i64>(file, ",");
+    let fps = option("--fps").map_or(20, |value| value.parse::<usize>().expect(USAGE)).max(1);
+    let delay = Duration::from_secs_f64(1.0 / fps as f64);
+
+    let outcome =
+        match option("--script") {
+            // Play the given keys without reading the terminal.
+            Some(script) => {
+                let script = Script::parse(script).unwrap_or_else(|c| panic!("Unknown key in the script: {:?}", c));
+                let mut player = Player::new(script, io::stdout(), delay);
+                player.autopilot = args.iter().any(|arg| arg == "--autopilot");
+                play::play(&code, player)
+            },
+            None => {
+                let (sender, receiver) = mpsc::channel();
+                let _raw_terminal = RawTerminal::new();
+                thread::spawn(move || read_keys(sender));
+                let mut player = Player::new(receiver, io::stdout(), delay);
+                player.autopilot = args.iter().any(|arg| arg == "--autopilot");
+                play::play(&code, player)
+            }
+        };
+
+    match outcome {
+        Ok(outcome) => println!("{}", outcome),
+        Err(error) => println!("Error: {:?}", error)
+    }
+}
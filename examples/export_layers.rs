@@ -0,0 +1,51 @@
+// Export the images of day 8 and day 11 part 2 as PBM, PGM, PPM and SVG files.
+// Usage: cargo run --example export_layers -- <directory> [--scale <factor>] [--cell <pixels>]
+// Example: cargo run --example export_layers -- images --scale 4 --cell 10
+
+use std::{env, fs, path::Path};
+
+use advent_of_code_2019::{common::{self, image}, day08, day11};
+
+const USAGE: &str = "Usage: export_layers <directory> [--scale <factor>] [--cell <pixels>]";
+
+fn export(directory: &Path, name: &str, layer: &[u8], width: usize, scale: usize, cell_size: usize) -> Result<(), image::ImageError> {
+    let (scaled, scaled_width) = image::scale(layer, width, scale)?;
+    let files = [
+        ("pbm", image::to_pbm(&scaled, scaled_width)?),
+        ("pgm", image::to_pgm(&scaled, scaled_width)?),
+        ("ppm", image::to_ppm(&scaled, scaled_width, &image::BLACK_AND_WHITE)?),
+        ("svg", image::to_svg(layer, width, cell_size, &image::BLACK_AND_WHITE)?.into_bytes()),
+    ];
+    for (extension, content) in files.iter() {
+        fs::write(directory.join(format!("{}.{}", name, extension)), content).unwrap();
+    }
+    println!("{} exported", name);
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let number = |name: &str, default: usize| args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1)).map_or(default, |value| value.parse::<usize>().expect(USAGE));
+
+    let directory = match args.first() {
+        Some(directory) if !directory.starts_with("--") => Path::new(directory),
+        _ => {
+            println!("{}", USAGE);
+            return
+        }
+    };
+    fs::create_dir_all(directory).unwrap();
+    let (scale, cell_size) = (number("--scale", 1), number("--cell", 10));
+
+    let image = day08::sif::Image::decode(&fs::read_to_string("data/day08.input").unwrap(), 25, 6).unwrap();
+    if let Err(error) = export(directory, "day08", &image.flatten(day08::sif::TRANSPARENT), 25, scale, cell_size) {
+        println!("{}", error);
+        return
+    }
+
+    let code = common::read_list_of_numbers::<&str, i64>("data/day11.input", ",");
+    let (layer, width) = day11::panels_to_layer(&day11::run_robot(&code, 1)).unwrap();
+    if let Err(error) = export(directory, "day11", &layer, width, scale, cell_size) {
+        println!("{}", error)
+    }
+}
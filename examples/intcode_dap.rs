@@ -0,0 +1,13 @@
+// Debug Adapter Protocol server for Intcode programs, it communicates through stdin and stdout.
+// Build it with 'cargo build --example intcode_dap' and configure the editor to launch 'target/debug/examples/intcode_dap'.
+// Launch arguments: "program" (path of the program), "input" (values separated by commas), "stopOnEntry" and "ascii".
+
+use std::io;
+
+use advent_of_code_2019::intcode::dap;
+
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    dap::serve(&mut stdin.lock(), &mut stdout.lock())
+}
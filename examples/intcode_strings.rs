@@ -0,0 +1,50 @@
+// Dump the strings embedded in an Intcode program and the output instructions which emit them.
+// Usage: cargo run --example intcode_strings -- <program file> [input values separated by commas] [--dump]
+// Example: cargo run --example intcode_strings -- data/day17.input --dump
+
+use std::env;
+
+use advent_of_code_2019::{common, intcode::{self, strings}};
+
+const MIN_LEN: usize = 4;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let dump = args.iter().any(|arg| arg == "--dump");
+    let args: Vec<&String> = args.iter().filter(|arg| *arg != "--dump").collect();
+
+    let file = match args.first() {
+        Some(file) => file,
+        None => {
+            println!("Usage: intcode_strings <program file> [input values separated by commas] [--dump]");
+            return
+        }
+    };
+
+    let code = common::read_list_of_numbers::<&str, i64>(file, ",");
+    let input: Vec<i64> =
+        match args.get(1) {
+            Some(input) => input.split(',').map(|value| value.trim().parse::<i64>().unwrap()).collect(),
+            None => Vec::new()
+        };
+
+    let mut outputs = Vec::new();
+    let result = strings::trace(&mut intcode::Machine::new(&code), &mut intcode::Buffer::from(&input), &mut outputs);
+    println!("Execution: {:?}, {} values written", result, outputs.len());
+
+    let mut texts = strings::scan(&code, MIN_LEN);
+    strings::link(&mut texts, &outputs);
+    println!("\nStrings in memory:");
+    for text in texts.iter() {
+        println!("{:>6}: {:?} emitted by {:?}", text.address, text.text, text.emitted_by);
+    }
+
+    println!("\nWritten messages:");
+    for message in strings::messages(&outputs, MIN_LEN) {
+        println!("{:?} written by {:?}", message.text, message.instructions);
+    }
+
+    if dump {
+        println!("\nMemory:\n{}", strings::hexdump(&code, 8));
+    }
+}
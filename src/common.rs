@@ -1,5 +1,9 @@
 use std::{fs, path::Path, str::FromStr};
 
+pub mod image;
+pub mod ocr;
+pub mod terminal;
+
 pub fn read_list_of_numbers<P, T>(file: P, sep: &str) -> Vec<T>
 where
     P: AsRef<Path>,
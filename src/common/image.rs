@@ -0,0 +1,158 @@
+// Export of layers (one value per pixel, row by row) as Netpbm images and SVG.
+
+use std::fmt;
+
+pub type Rgb = [u8; 3];
+
+// Black for 0 and white for 1 like in day 8.
+pub const BLACK_AND_WHITE: [Rgb; 2] = [[0, 0, 0], [255, 255, 255]];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageError {
+    InvalidWidth { len: usize, width: usize }, // The length of the layer isn't a multiple of the width.
+    UnknownColor { index: usize, value: u8 }, // The value of the pixel 'index' isn't in the palette.
+    ZeroScale, // The image would be empty.
+    ZeroCellSize, // The cells of an SVG would be empty.
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::InvalidWidth { len, width } => write!(f, "A layer of {} pixels can't have a width of {}", len, width),
+            ImageError::UnknownColor { index, value } => write!(f, "No color for the value {} of the pixel {}", value, index),
+            ImageError::ZeroScale => write!(f, "The scale factor can't be 0"),
+            ImageError::ZeroCellSize => write!(f, "The cell size can't be 0")
+        }
+    }
+}
+
+fn height<T>(layer: &[T], width: usize) -> Result<usize, ImageError> {
+    if width == 0 || layer.len() % width != 0 {
+        return Err(ImageError::InvalidWidth { len: layer.len(), width })
+    }
+    Ok(layer.len() / width)
+}
+
+fn colors(layer: &[u8], palette: &[Rgb]) -> Result<Vec<Rgb>, ImageError> {
+    layer.iter().enumerate().map(|(index, value)| palette.get(*value as usize).copied().ok_or(ImageError::UnknownColor { index, value: *value })).collect()
+}
+
+// Each pixel repeated 'factor' times horizontally and vertically, return the new layer and its width.
+pub fn scale<T: Copy>(layer: &[T], width: usize, factor: usize) -> Result<(Vec<T>, usize), ImageError> {
+    height(layer, width)?;
+    if factor == 0 {
+        return Err(ImageError::ZeroScale)
+    }
+    let mut result = Vec::with_capacity(layer.len() * factor * factor);
+    for row in layer.chunks(width) {
+        let scaled_row: Vec<T> = row.iter().flat_map(|pixel| std::iter::repeat(*pixel).take(factor)).collect();
+        for _ in 0 .. factor {
+            result.extend_from_slice(&scaled_row);
+        }
+    }
+    Ok((result, width * factor))
+}
+
+// Binary PBM (P4), the pixels with the value 0 are black, the others white.
+pub fn to_pbm(layer: &[u8], width: usize) -> Result<Vec<u8>, ImageError> {
+    let height = height(layer, width)?;
+    let mut image = format!("P4\n{} {}\n", width, height).into_bytes();
+    for row in layer.chunks(width) {
+        // 8 pixels per byte, 1 is black.
+        image.extend(row.chunks(8).map(|pixels| pixels.iter().enumerate().fold(0u8, |byte, (i, pixel)| if *pixel == 0 { byte | 0x80 >> i } else { byte })));
+    }
+    Ok(image)
+}
+
+// Binary PGM (P5), the values are the gray levels up to the largest one.
+pub fn to_pgm(layer: &[u8], width: usize) -> Result<Vec<u8>, ImageError> {
+    let height = height(layer, width)?;
+    let max_value = layer.iter().copied().max().unwrap_or(0).max(1);
+    let mut image = format!("P5\n{} {}\n{}\n", width, height, max_value).into_bytes();
+    image.extend_from_slice(layer);
+    Ok(image)
+}
+
+// Binary PPM (P6) of the pixels colors.
+pub fn rgb_to_ppm(pixels: &[Rgb], width: usize) -> Result<Vec<u8>, ImageError> {
+    let height = height(pixels, width)?;
+    let mut image = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    image.extend(pixels.iter().flatten());
+    Ok(image)
+}
+
+// Binary PPM (P6), the values are indices in the palette.
+pub fn to_ppm(layer: &[u8], width: usize, palette: &[Rgb]) -> Result<Vec<u8>, ImageError> {
+    rgb_to_ppm(&colors(layer, palette)?, width)
+}
+
+// One rectangle of 'cell_size' pixels side per run of pixels of the same value in a row, the values are indices
+// in the palette.
+pub fn to_svg(layer: &[u8], width: usize, cell_size: usize, palette: &[Rgb]) -> Result<String, ImageError> {
+    let height = height(layer, width)?;
+    let colors = colors(layer, palette)?;
+    if cell_size == 0 {
+        return Err(ImageError::ZeroCellSize)
+    }
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n", width * cell_size, height * cell_size, width * cell_size, height * cell_size);
+    for (y, row) in colors.chunks(width).enumerate() {
+        let mut x = 0;
+        while x < width {
+            let len = row[x ..].iter().take_while(|color| **color == row[x]).count();
+            let [r, g, b] = row[x];
+            svg += &format!("  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#{:02x}{:02x}{:02x}\"/>\n", x * cell_size, y * cell_size, len * cell_size, cell_size, r, g, b);
+            x += len;
+        }
+    }
+    svg += "</svg>\n";
+    Ok(svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 3x2: 0 1 1 / 1 0 2
+    const LAYER: [u8; 6] = [0, 1, 1, 1, 0, 2];
+    const PALETTE: [Rgb; 3] = [[0, 0, 0], [255, 255, 255], [255, 0, 0]];
+
+    #[test]
+    fn netpbm() {
+        assert_eq!(to_pbm(&LAYER, 3), Ok([&b"P4\n3 2\n"[..], &[0b1000_0000, 0b0100_0000]].concat()));
+        assert_eq!(to_pgm(&LAYER, 3), Ok([&b"P5\n3 2\n2\n"[..], &LAYER].concat()));
+        assert_eq!(to_ppm(&LAYER, 3, &PALETTE), Ok([&b"P6\n3 2\n255\n"[..], &[0, 0, 0, 255, 255, 255, 255, 255, 255, 255, 255, 255, 0, 0, 0, 255, 0, 0]].concat()));
+
+        // A row of more than 8 pixels takes several bytes.
+        assert_eq!(to_pbm(&[0; 9], 9), Ok([&b"P4\n9 1\n"[..], &[0xff, 0x80]].concat()));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(to_pgm(&LAYER, 4), Err(ImageError::InvalidWidth { len: 6, width: 4 }));
+        assert_eq!(to_ppm(&LAYER, 3, &BLACK_AND_WHITE), Err(ImageError::UnknownColor { index: 5, value: 2 }));
+        assert_eq!(ImageError::UnknownColor { index: 5, value: 2 }.to_string(), "No color for the value 2 of the pixel 5");
+    }
+
+    #[test]
+    fn scaled() {
+        assert_eq!(scale(&[1, 2, 3, 4], 2, 2), Ok((vec![1, 1, 2, 2, 1, 1, 2, 2, 3, 3, 4, 4, 3, 3, 4, 4], 4)));
+        let (layer, width) = scale(&LAYER, 3, 3).unwrap();
+        assert!(to_pgm(&layer, width).unwrap().starts_with(b"P5\n9 6\n2\n"));
+        assert_eq!(scale(&LAYER, 3, 0), Err(ImageError::ZeroScale));
+    }
+
+    #[test]
+    fn svg() {
+        assert_eq!(
+            to_svg(&LAYER, 3, 10, &PALETTE),
+            Ok(String::from(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"30\" height=\"20\" viewBox=\"0 0 30 20\">\n\
+                 \x20 <rect x=\"0\" y=\"0\" width=\"10\" height=\"10\" fill=\"#000000\"/>\n\
+                 \x20 <rect x=\"10\" y=\"0\" width=\"20\" height=\"10\" fill=\"#ffffff\"/>\n\
+                 \x20 <rect x=\"0\" y=\"10\" width=\"10\" height=\"10\" fill=\"#ffffff\"/>\n\
+                 \x20 <rect x=\"10\" y=\"10\" width=\"10\" height=\"10\" fill=\"#000000\"/>\n\
+                 \x20 <rect x=\"20\" y=\"10\" width=\"10\" height=\"10\" fill=\"#ff0000\"/>\n\
+                 </svg>\n")));
+        assert_eq!(to_svg(&LAYER, 3, 0, &PALETTE), Err(ImageError::ZeroCellSize));
+    }
+}
@@ -0,0 +1,194 @@
+// Recognition of the capital letters drawn by some puzzles, like the answers of day 8 and day 11.
+// The non-zero pixels of a layer are the lit ones.
+
+use std::fmt;
+
+pub struct Font {
+    pub width: usize,
+    pub height: usize,
+    glyphs: &'static [(char, &'static [&'static str])], // One string per row, '#' for a lit pixel.
+}
+
+// The 4x6 font, letters are separated by one column.
+pub const SMALL: Font = Font {
+    width: 4,
+    height: 6,
+    glyphs: &[
+        ('A', &[".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+        ('B', &["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+        ('C', &[".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+        ('E', &["####", "#...", "###.", "#...", "#...", "####"]),
+        ('F', &["####", "#...", "###.", "#...", "#...", "#..."]),
+        ('G', &[".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+        ('H', &["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+        ('I', &[".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+        ('J', &["..##", "...#", "...#", "...#", "#..#", ".##."]),
+        ('K', &["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+        ('L', &["#...", "#...", "#...", "#...", "#...", "####"]),
+        ('O', &[".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+        ('P', &["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+        ('R', &["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+        ('S', &[".###", "#...", "#...", ".##.", "...#", "###."]),
+        ('U', &["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+        ('Z', &["####", "...#", "..#.", ".#..", "#...", "####"]),
+    ]
+};
+
+// The 6x10 font, letters are separated by two columns.
+pub const LARGE: Font = Font {
+    width: 6,
+    height: 10,
+    glyphs: &[
+        ('A', &["..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#"]),
+        ('B', &["#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#", "#....#", "#####."]),
+        ('C', &[".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#....#", ".####."]),
+        ('E', &["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "######"]),
+        ('F', &["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+        ('G', &[".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#", "#...##", ".###.#"]),
+        ('H', &["#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#", "#....#"]),
+        ('J', &["...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.", "#...#.", ".###.."]),
+        ('K', &["#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..", "#...#.", "#....#"]),
+        ('L', &["#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "######"]),
+        ('N', &["#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##", "#...##", "#....#"]),
+        ('P', &["#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+        ('R', &["#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.", "#....#", "#....#"]),
+        ('X', &["#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.", "#....#", "#....#"]),
+        ('Z', &["######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....", "#.....", "######"]),
+    ]
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unrecognized {
+    pub column: usize, // First column of the glyph in the layer.
+    pub rows: Vec<String>, // The pixels of the glyph like in the fonts.
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recognized {
+    pub text: String, // With '?' for each unrecognized glyph.
+    pub unrecognized: Vec<Unrecognized>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OcrError {
+    InvalidWidth { len: usize, width: usize },
+    InvalidHeight { height: usize, expected: usize }, // Without the empty rows at the top and the bottom.
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OcrError::InvalidWidth { len, width } => write!(f, "A layer of {} pixels can't have a width of {}", len, width),
+            OcrError::InvalidHeight { height, expected } => write!(f, "The text is {} pixels high instead of {}", height, expected)
+        }
+    }
+}
+
+impl Font {
+    fn is_lit(&self, glyph: &[&str], x: usize, y: usize) -> bool {
+        glyph[y].as_bytes()[x] == b'#'
+    }
+
+    // Number of empty columns on the left of a glyph.
+    fn margin(&self, glyph: &[&str]) -> usize {
+        (0 .. self.width).take_while(|x| (0 .. self.height).all(|y| !self.is_lit(glyph, *x, y))).count()
+    }
+}
+
+pub fn recognize(layer: &[u8], width: usize, font: &Font) -> Result<Recognized, OcrError> {
+    if width == 0 || layer.len() % width != 0 {
+        return Err(OcrError::InvalidWidth { len: layer.len(), width })
+    }
+    let rows: Vec<&[u8]> = layer.chunks(width).skip_while(|row| row.iter().all(|pixel| *pixel == 0)).collect();
+    let empty_at_bottom = rows.iter().rev().take_while(|row| row.iter().all(|pixel| *pixel == 0)).count();
+    let rows = &rows[.. rows.len() - empty_at_bottom];
+    if rows.len() != font.height {
+        return Err(OcrError::InvalidHeight { height: rows.len(), expected: font.height })
+    }
+
+    // Pixels outside of the layer are off.
+    let is_lit = |x: usize, y: usize| x < width && rows[y][x] != 0;
+    let is_empty_column = |x: usize| (0 .. font.height).all(|y| !is_lit(x, y));
+    let matches = |glyph: &[&str], start: usize| (0 .. font.width).all(|dx| (0 .. font.height).all(|y| is_lit(start + dx, y) == font.is_lit(glyph, dx, y)));
+
+    let mut recognized = Recognized { text: String::new(), unrecognized: Vec::new() };
+    let mut x = 0;
+    while x < width {
+        if is_empty_column(x) {
+            x += 1;
+            continue
+        }
+
+        // 'x' is the first lit column of the glyph.
+        let found = font.glyphs.iter().find_map(|(c, glyph)| {
+            let start = x.checked_sub(font.margin(glyph))?;
+            if matches(glyph, start) { Some((*c, start)) } else { None }
+        });
+        match found {
+            Some((c, start)) => {
+                recognized.text.push(c);
+                x = start + font.width;
+            },
+            None => {
+                recognized.text.push('?');
+                let rows = (0 .. font.height).map(|y| (x .. x + font.width).map(|x| if is_lit(x, y) { '#' } else { '.' }).collect()).collect();
+                recognized.unrecognized.push(Unrecognized { column: x, rows });
+                x += font.width;
+            }
+        }
+    }
+    Ok(recognized)
+}
+
+// Draw a text with the font and 'spacing' empty columns between the letters, return the layer and its width.
+// Return the first character not in the font as error.
+pub fn render(text: &str, font: &Font, spacing: usize) -> Result<(Vec<u8>, usize), char> {
+    let glyphs = text.chars().map(|c| font.glyphs.iter().find(|(g, _)| *g == c).map(|(_, glyph)| *glyph).ok_or(c)).collect::<Result<Vec<_>, char>>()?;
+    let width = (glyphs.len() * (font.width + spacing)).saturating_sub(spacing);
+    let mut layer = vec![0; width * font.height];
+    for (i, glyph) in glyphs.iter().enumerate() {
+        for y in 0 .. font.height {
+            for dx in 0 .. font.width {
+                layer[y * width + i * (font.width + spacing) + dx] = font.is_lit(glyph, dx, y) as u8;
+            }
+        }
+    }
+    Ok((layer, width))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_font() {
+        let (layer, width) = render("ZRZPKEZR", &SMALL, 1).unwrap();
+        assert_eq!(recognize(&layer, width, &SMALL), Ok(Recognized { text: String::from("ZRZPKEZR"), unrecognized: vec![] }));
+
+        // 'I' starts with an empty column, the whole text is shifted and surrounded by empty rows.
+        let (layer, width) = render("ICHIJ", &SMALL, 1).unwrap();
+        let mut shifted = vec![0; (width + 3) * 8];
+        for (i, pixel) in layer.iter().enumerate() {
+            shifted[(i / width + 1) * (width + 3) + i % width + 2] = *pixel;
+        }
+        assert_eq!(recognize(&shifted, width + 3, &SMALL).unwrap().text, "ICHIJ");
+    }
+
+    #[test]
+    fn large_font() {
+        let (layer, width) = render("HELLXZ", &LARGE, 2).unwrap();
+        assert_eq!(recognize(&layer, width, &LARGE).unwrap().text, "HELLXZ");
+        assert_eq!(recognize(&layer, width, &SMALL), Err(OcrError::InvalidHeight { height: 10, expected: 6 }));
+    }
+
+    #[test]
+    fn unrecognized_glyphs() {
+        let (mut layer, width) = render("AHA", &SMALL, 1).unwrap();
+        layer[width + 5 + 1] = 1; // An extra pixel in the 'H'.
+        let recognized = recognize(&layer, width, &SMALL).unwrap();
+        assert_eq!(recognized.text, "A?A");
+        assert_eq!(recognized.unrecognized, vec![Unrecognized { column: 5, rows: ["#..#", "##.#", "####", "#..#", "#..#", "#..#"].iter().map(|row| row.to_string()).collect() }]);
+
+        assert_eq!(render("A1", &SMALL, 1), Err('1'));
+    }
+}
@@ -0,0 +1,157 @@
+// Rendering of layers in a terminal with two rows of pixels per line of text.
+
+use std::fmt::Write;
+
+use super::image::{ImageError, Rgb};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Color {
+    pub value: u8,
+    pub rgb: Rgb,
+    pub label: String, // Shown in the legend.
+}
+
+impl Color {
+    pub fn new(value: u8, rgb: Rgb, label: &str) -> Self {
+        Color { value, rgb, label: String::from(label) }
+    }
+}
+
+// The colors of day 8, transparent pixels are gray.
+pub fn day08_colors() -> Vec<Color> {
+    vec![Color::new(0, [0, 0, 0], "black"), Color::new(1, [255, 255, 255], "white"), Color::new(2, [128, 128, 128], "transparent")]
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Renderer {
+    pub colors: Vec<Color>, // Without colors the non-zero pixels are drawn with block characters and the others are empty.
+    pub frame: bool, // Surround the image with a border and the coordinates of the pixels.
+    pub legend: bool, // Show the colors and their label after the image.
+}
+
+fn foreground(rgb: Rgb) -> String { format!("\x1b[38;2;{};{};{}m", rgb[0], rgb[1], rgb[2]) }
+
+fn background(rgb: Rgb) -> String { format!("\x1b[48;2;{};{};{}m", rgb[0], rgb[1], rgb[2]) }
+
+const RESET: &str = "\x1b[0m";
+
+impl Renderer {
+    pub fn monochrome() -> Self { Renderer::default() }
+
+    pub fn with_colors(colors: Vec<Color>) -> Self {
+        Renderer { colors, ..Renderer::default() }
+    }
+
+    fn rgb(&self, layer: &[u8], index: usize) -> Result<Rgb, ImageError> {
+        let value = layer[index];
+        self.colors.iter().find(|color| color.value == value).map(|color| color.rgb).ok_or(ImageError::UnknownColor { index, value })
+    }
+
+    // One line of text for the rows 'y' and 'y + 1' (if any).
+    fn render_line(&self, layer: &[u8], width: usize, y: usize) -> Result<String, ImageError> {
+        let has_bottom = (y + 1) * width < layer.len();
+        let mut line = String::new();
+
+        if self.colors.is_empty() {
+            for x in 0 .. width {
+                let top = layer[y * width + x] != 0;
+                let bottom = has_bottom && layer[(y + 1) * width + x] != 0;
+                line.push(match (top, bottom) { (true, true) => '█', (true, false) => '▀', (false, true) => '▄', (false, false) => ' ' });
+            }
+            return Ok(line)
+        }
+
+        // The top pixel is the foreground of an upper half block and the bottom one its background.
+        let mut current = (None, None);
+        for x in 0 .. width {
+            let top = self.rgb(layer, y * width + x)?;
+            let bottom = if has_bottom { Some(self.rgb(layer, (y + 1) * width + x)?) } else { None };
+            if current.0 != Some(top) {
+                line += &foreground(top);
+            }
+            if current.1 != Some(bottom) {
+                line += &bottom.map_or(String::from("\x1b[49m"), background);
+            }
+            current = (Some(top), Some(bottom));
+            line.push('▀');
+        }
+        line += RESET;
+        Ok(line)
+    }
+
+    pub fn render(&self, layer: &[u8], width: usize) -> Result<String, ImageError> {
+        if width == 0 || layer.len() % width != 0 {
+            return Err(ImageError::InvalidWidth { len: layer.len(), width })
+        }
+        let height = layer.len() / width;
+        let lines = (0 .. height).step_by(2).map(|y| self.render_line(layer, width, y)).collect::<Result<Vec<String>, ImageError>>()?;
+
+        let mut result = String::new();
+        if self.frame {
+            // The Y coordinate of the top row of each line and the X coordinate of one column out of five.
+            let margin = height.saturating_sub(1).to_string().len();
+            let mut header = String::new();
+            for x in (0 .. width).step_by(5) {
+                write!(header, "{:<5}", x).unwrap();
+            }
+            writeln!(result, "{:margin$}  {}", "", header.trim_end(), margin = margin).unwrap();
+            writeln!(result, "{:margin$} ┌{}┐", "", "─".repeat(width), margin = margin).unwrap();
+            for (i, line) in lines.iter().enumerate() {
+                writeln!(result, "{:>margin$} │{}│", i * 2, line, margin = margin).unwrap();
+            }
+            write!(result, "{:margin$} └{}┘", "", "─".repeat(width), margin = margin).unwrap();
+        } else {
+            result += &lines.join("\n");
+        }
+
+        if self.legend {
+            for color in self.colors.iter() {
+                write!(result, "\n{}██{} {}: {}", foreground(color.rgb), RESET, color.value, color.label).unwrap();
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 3x3: 1 0 1 / 1 1 0 / 2 0 1
+    const LAYER: [u8; 9] = [1, 0, 1, 1, 1, 0, 2, 0, 1];
+
+    #[test]
+    fn half_blocks() {
+        assert_eq!(Renderer::monochrome().render(&LAYER, 3), Ok(String::from("█▄▀\n▀ ▀")));
+        assert_eq!(Renderer::monochrome().render(&LAYER, 4), Err(ImageError::InvalidWidth { len: 9, width: 4 }));
+    }
+
+    #[test]
+    fn colors_and_legend() {
+        let renderer = Renderer { colors: vec![Color::new(0, [0, 0, 0], "off"), Color::new(1, [255, 255, 255], "on")], frame: false, legend: true };
+        assert_eq!(
+            renderer.render(&LAYER[.. 6], 3),
+            Ok(String::from("\x1b[38;2;255;255;255m\x1b[48;2;255;255;255m▀\x1b[38;2;0;0;0m▀\x1b[38;2;255;255;255m\x1b[48;2;0;0;0m▀\x1b[0m\n\
+                             \x1b[38;2;0;0;0m██\x1b[0m 0: off\n\
+                             \x1b[38;2;255;255;255m██\x1b[0m 1: on")));
+
+        // The last row is alone on its line.
+        let line = renderer.render_line(&LAYER, 3, 2);
+        assert_eq!(line, Err(ImageError::UnknownColor { index: 6, value: 2 }));
+        let renderer = Renderer::with_colors(day08_colors());
+        assert_eq!(renderer.render_line(&LAYER, 3, 2), Ok(String::from("\x1b[38;2;128;128;128m\x1b[49m▀\x1b[38;2;0;0;0m▀\x1b[38;2;255;255;255m▀\x1b[0m")));
+    }
+
+    #[test]
+    fn coordinates_frame() {
+        let layer = vec![1; 12 * 11];
+        let renderer = Renderer { frame: true, ..Renderer::monochrome() };
+        let lines: Vec<String> = renderer.render(&layer, 12).unwrap().lines().map(String::from).collect();
+        assert_eq!(lines.len(), 2 + 6 + 1);
+        assert_eq!(lines[0], "    0    5    10");
+        assert_eq!(lines[1], "   ┌────────────┐");
+        assert_eq!(lines[2], " 0 │████████████│");
+        assert_eq!(lines[7], "10 │▀▀▀▀▀▀▀▀▀▀▀▀│");
+        assert_eq!(lines[8], "   └────────────┘");
+    }
+}
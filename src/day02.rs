@@ -1,35 +1,115 @@
-pub fn execute_op_code_with_state_fixed(code: &mut [i32]) -> i32 {
-    code[1] = 12;
-    code[2] = 2;
-    execute_op_code(code)
-}
-
-fn execute_op_code(code: &mut [i32]) -> i32 {
-    let mut cursor = 0;
-    loop {
-        match code[cursor] {
-            1 => code[code[cursor + 3] as usize] = code[code[cursor + 1] as usize] + code[code[cursor + 2] as usize],
-            2 => code[code[cursor + 3] as usize] = code[code[cursor + 1] as usize] * code[code[cursor + 2] as usize],
-            99 => return code[0],
-            _ => panic!("Unknown code: {}", code[cursor])
+use std::ops::RangeInclusive;
+
+use super::intcode::{self, InstructionSet, Machine, Patch};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    NoSolutionInRange,
+    Intcode(intcode::Error),
+}
+
+impl From<intcode::Error> for Error {
+    fn from(error: intcode::Error) -> Self { Error::Intcode(error) }
+}
+
+// Restore the gravity assist program to the "1202 program alarm" state it had just before the last computer caught fire.
+pub fn restore_1202_program_alarm() -> Patch {
+    Patch::new("1202 program alarm", &[(1, 12), (2, 2)])
+}
+
+pub fn execute_op_code_with_state_fixed(code: &[i64]) -> i64 {
+    execute_op_code(&restore_1202_program_alarm().apply(code)).unwrap()[0]
+}
+
+// Return the memory once the program has halted.
+fn execute_op_code(code: &[i64]) -> Result<Vec<i64>, intcode::Error> {
+    let mut machine = Machine::with_instruction_set(code, InstructionSet::Day02);
+    machine.run(&mut intcode::Buffer::new())?;
+    Ok(machine.memory().to_vec())
+}
+
+fn output(code: &[i64], noun: i64, verb: i64) -> Result<i64, intcode::Error> {
+    Ok(execute_op_code(&Patch::noun_and_verb(noun, verb).apply(code))?[0])
+}
+
+// Output is 'constant + noun_factor * noun + verb_factor * verb'.
+struct Affine { constant: i64, noun_factor: i64, verb_factor: i64 }
+
+impl Affine {
+    // Return 'None' on overflow.
+    fn eval(&self, noun: i64, verb: i64) -> Option<i64> {
+        self.noun_factor.checked_mul(noun)?.checked_add(self.verb_factor.checked_mul(verb)?)?.checked_add(self.constant)
+    }
+}
+
+// Probe a few runs to find the affine function of the output, return 'None' if the output isn't affine.
+fn probe_affine(code: &[i64], range: &RangeInclusive<i64>) -> Result<Option<Affine>, intcode::Error> {
+    let (min, max) = (*range.start(), *range.end());
+    if max <= min { return Ok(None) }
+
+    let origin = output(code, min, min)?;
+    let (noun_factor, verb_factor) =
+        match (output(code, min + 1, min)?.checked_sub(origin), output(code, min, min + 1)?.checked_sub(origin)) {
+            (Some(noun_factor), Some(verb_factor)) => (noun_factor, verb_factor),
+            _ => return Ok(None)
+        };
+    let constant = match noun_factor.checked_add(verb_factor).and_then(|f| f.checked_mul(min)).and_then(|f| origin.checked_sub(f)) {
+        Some(constant) => constant,
+        None => return Ok(None)
+    };
+    let affine = Affine { constant, noun_factor, verb_factor };
+
+    for (noun, verb) in [(min + 1, min + 1), (max, min), (min, max), (max, max)] {
+        if Some(output(code, noun, verb)?) != affine.eval(noun, verb) {
+            return Ok(None)
         }
-        cursor += 4;
     }
+
+    Ok(Some(affine))
 }
 
-pub fn find_noun_and_verb(code: &[i32]) -> i32 {
-    loop {
-        for verb in 0..=99 {
-            for noun in 0..=99 {
-                let mut code_copy = Vec::from(code);
-                code_copy[1] = noun;
-                code_copy[2] = verb;
-                if execute_op_code(&mut code_copy) == 19_690_720 {
-                    return 100 * noun + verb
+// Return the noun solving 'affine(noun, verb) == target' for the given verb.
+fn solve_noun(affine: &Affine, target: i64, verb: i64, range: &RangeInclusive<i64>) -> Option<i64> {
+    let remainder = target.checked_sub(affine.eval(0, verb)?)?;
+    if affine.noun_factor == 0 {
+        if remainder == 0 { Some(*range.start()) } else { None }
+    } else if remainder % affine.noun_factor == 0 && range.contains(&(remainder / affine.noun_factor)) {
+        Some(remainder / affine.noun_factor)
+    } else {
+        None
+    }
+}
+
+// Return the first noun and verb (ordered by verb then noun) for which the output is 'target'.
+pub fn find_noun_and_verb(code: &[i64], target: i64, range: RangeInclusive<i64>) -> Result<(i64, i64), Error> {
+    if let Some(affine) = probe_affine(code, &range)? {
+        let mut model_is_wrong = false;
+        for verb in range.clone() {
+            if let Some(noun) = solve_noun(&affine, target, verb, &range) {
+                // Validate the answer.
+                if output(code, noun, verb)? == target {
+                    return Ok((noun, verb))
                 }
+                model_is_wrong = true;
+                break
             }
         }
+
+        if !model_is_wrong {
+            return Err(Error::NoSolutionInRange)
+        }
     }
+
+    // The output isn't affine (or the probe was wrong): brute force.
+    for verb in range.clone() {
+        for noun in range.clone() {
+            if output(code, noun, verb)? == target {
+                return Ok((noun, verb))
+            }
+        }
+    }
+
+    Err(Error::NoSolutionInRange)
 }
 
 #[cfg(test)]
@@ -38,20 +118,51 @@ mod tests {
 
     #[test]
     fn simple_cases() {
-        let mut c1 = [1, 0, 0, 0, 99];
-        execute_op_code(&mut c1);
+        let c1 = execute_op_code(&[1, 0, 0, 0, 99]).unwrap();
         assert_eq!(c1[0], 2);
 
-        let mut c2 = [2, 3, 0, 3, 99];
-        execute_op_code(&mut c2);
+        let c2 = execute_op_code(&[2, 3, 0, 3, 99]).unwrap();
         assert_eq!(c2[3], 6);
 
-        let mut c3 = [2, 4, 4, 5, 99, 0];
-        execute_op_code(&mut c3);
+        let c3 = execute_op_code(&[2, 4, 4, 5, 99, 0]).unwrap();
         assert_eq!(c3[5], 9801);
 
-        let mut c4 = [1, 1, 1, 4, 99, 5, 6, 0, 99];
-        execute_op_code(&mut c4);
+        let c4 = execute_op_code(&[1, 1, 1, 4, 99, 5, 6, 0, 99]).unwrap();
         assert_eq!(c4[0], 30);
     }
+
+    // Output: 3 * noun + verb + 7.
+    const AFFINE: [i64; 20] = [1, 0, 0, 19, 2, 1, 17, 0, 1, 0, 2, 0, 1, 0, 18, 0, 99, 3, 7, 0];
+
+    #[test]
+    fn affine_noun_and_verb() {
+        assert_eq!(find_noun_and_verb(&AFFINE, 77, 0 ..= 99), Ok((23, 1)));
+        assert_eq!(find_noun_and_verb(&AFFINE, 3 * 61 + 42 + 7, 42 ..= 99), Ok((61, 42)));
+    }
+
+    #[test]
+    fn no_solution_in_range() {
+        assert_eq!(find_noun_and_verb(&AFFINE, 1000, 0 ..= 9), Err(Error::NoSolutionInRange));
+
+        // A brute force over this range would never end.
+        assert_eq!(find_noun_and_verb(&AFFINE, 1000, 0 ..= 1_000_000), Ok((331, 0)));
+        assert_eq!(find_noun_and_verb(&AFFINE, 6, 0 ..= 1_000_000), Err(Error::NoSolutionInRange));
+    }
+
+    #[test]
+    fn affine_overflow() {
+        let affine = Affine { constant: 1, noun_factor: i64::MAX / 2, verb_factor: 1 };
+        assert_eq!(affine.eval(2, 0), Some(i64::MAX));
+        assert_eq!(affine.eval(2, 1), None);
+        assert_eq!(affine.eval(3, 0), None);
+        assert_eq!(solve_noun(&affine, i64::MIN, 0, &(0 ..= 99)), None);
+    }
+
+    #[test]
+    fn not_affine_noun_and_verb() {
+        // Output: noun * verb.
+        let code = [1, 0, 0, 19, 2, 1, 2, 0, 99];
+        assert_eq!(find_noun_and_verb(&code, 12, 0 ..= 9), Ok((6, 2)));
+        assert_eq!(find_noun_and_verb(&code, 13, 0 ..= 9), Err(Error::NoSolutionInRange));
+    }
 }
\ No newline at end of file
@@ -1,96 +1,71 @@
-use std::sync::{Arc, Barrier, mpsc::{self, Sender, Receiver}, atomic::{AtomicI64, Ordering}};
+use std::sync::Arc;
 
-use itertools::Itertools;
 use threadpool::ThreadPool;
 
 use super::intcode;
 
+pub mod network;
+pub mod report;
+pub mod search;
+
+use network::{Network, NetworkError};
+use report::Report;
+use search::{Search, Solution};
+
+#[cfg(test)]
 fn last_thruster_signal(code: &[i64], phase_setting: &[i64]) -> i64 {
-    phase_setting.iter().fold(0, |last_output, input| intcode::execute_op_code(&code, &[*input, last_output])[0])
+    phase_setting.iter().fold(0, |last_output, input| intcode::execute_op_code(code, &[*input, last_output])[0])
 }
 
-pub fn find_largest_last_thruster_signal(code: &[i64]) -> i64 {
-    (0i64 ..= 4i64).permutations(5).map(|phase_setting| last_thruster_signal(&code, &phase_setting)).max().unwrap()
+// The first value sent by an amplifier of a chain receiving its phase then the signal.
+fn amplify(code: &[i64], stage: usize, signal: i64, phase: i64) -> Result<i64, NetworkError> {
+    let mut buffer = intcode::Buffer::from(&[phase, signal]);
+    intcode::Machine::new(code).run(&mut buffer).map_err(|error| NetworkError::Intcode { stage, error })?;
+    buffer.output.first().copied().ok_or(NetworkError::NoOutput)
 }
 
-struct Stage {
-    input_channel: mpsc::Receiver<i64>,
-    output_channel: mpsc::Sender<i64>,
-    last_produced_value: i64
+// The permutation of 'phases' giving the largest signal at the end of a chain of amplifiers.
+pub fn best_chain_phase_setting(code: &[i64], phases: &[i64], pool: Option<&ThreadPool>) -> Result<Option<Solution>, NetworkError> {
+    let code: Arc<[i64]> = Arc::from(code);
+    Search::permutations(phases, phases.len()).solve_chain(pool, 0, move |stage, signal, phase| amplify(&code, stage, signal, phase))
 }
 
-impl intcode::IO for Stage {
-    // May block.
-    fn read(&mut self) -> i64 {
-        match self.input_channel.recv() {
-            Ok(value) => value,
-            Err(_) => 0
-        }
-    }
-
-    // Send to the output channel.
-    fn write(&mut self, value: i64) {
-        self.last_produced_value = value;
-        self.output_channel.send(value).unwrap_or_default();
-    }
+pub fn find_largest_last_thruster_signal(code: &[i64], pool: Option<&ThreadPool>) -> i64 {
+    best_chain_phase_setting(code, &[0, 1, 2, 3, 4], pool).unwrap().unwrap().output
 }
 
-fn last_thruster_signal_with_feedback_loop(code: &[i64], phase_setting: &[i64], pool: &ThreadPool) -> i64 {
-    let n = phase_setting.len();
-
-    let mut senders = Vec::<Sender<i64>>::new();
-    let mut receivers = Vec::<Receiver<i64>>::new();
-
-    for (i, (s, r)) in (0 .. n).map(|i| (i, mpsc::channel::<i64>())) {
-        // Initial values.
-        s.send(phase_setting[i]).unwrap_or_default();
-        if i == 0 { s.send(0).unwrap_or_default(); }
-
-        senders.insert(if i == 0 { 0 } else { i - 1 }, s);
-        receivers.push(r);
-    }
+// Run the amplifiers one after the other, each one receiving its phase setting then all the outputs of the previous one.
+pub fn chain_report(code: &[i64], phase_setting: &[i64]) -> Report {
+    Network::chain(phase_setting.len()).report(code, phase_setting).expect("One phase per stage by construction")
+}
 
-    // Prepare each pair of received and sender for the each stages.
-    let mut channels: Vec<(Receiver<i64>, Sender<i64>)> = receivers.drain(..).zip(senders.drain(..)).collect();
-
-    let result = Arc::new(AtomicI64::new(0));
-    let barrier = Arc::new(Barrier::new(2));
-
-    for (i, (receiver, sender)) in channels.drain(..).enumerate() {
-        let code_copy = Vec::<i64>::from(code);
-        let barrier = barrier.clone();
-        let result = result.clone();
-
-        pool.execute(
-            move || {
-                let mut stage = Stage { input_channel: receiver, output_channel: sender, last_produced_value: 0 };
-                intcode::execute_op_code_with_custom_io(&code_copy, &mut stage);
-                if i == 4 {
-                    result.store(stage.last_produced_value, Ordering::Relaxed);
-                    barrier.wait();
-                }
-            }
-        )
-    }
+// Run the amplifiers in a ring, each one sending its outputs to the next one and the last one to the first one.
+pub fn feedback_loop_report(code: &[i64], phase_setting: &[i64]) -> Report {
+    Network::ring(phase_setting.len()).report(code, phase_setting).expect("One phase per stage by construction")
+}
 
-    barrier.wait();
-    result.load(Ordering::Relaxed)
+#[cfg(test)]
+fn last_thruster_signal_with_feedback_loop(code: &[i64], phase_setting: &[i64]) -> i64 {
+    feedback_loop_report(code, phase_setting).signal.unwrap_or_default()
 }
 
-pub fn find_largest_last_thruster_signal_with_feedback_loop(code: &[i64]) -> i64 {
-    let pool = ThreadPool::new(5);
-    (5i64 ..= 9i64).permutations(5).map(|phase_setting| last_thruster_signal_with_feedback_loop(&code, &phase_setting, &pool)).max().unwrap()
+pub fn find_largest_last_thruster_signal_with_feedback_loop(code: &[i64], pool: Option<&ThreadPool>) -> i64 {
+    Network::ring(5).best_phase_setting(code, &Search::permutations(&[5, 6, 7, 8, 9], 5), pool).unwrap().unwrap().output
 }
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools;
+
     use super::*;
+    use report::{StageReport, Termination};
 
     #[test]
     fn part1_sample_1() {
         let code = vec![3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0];
         let phase_setting = [4,3,2,1,0];
         assert_eq!(last_thruster_signal(&code, &phase_setting), 43210);
+        assert_eq!(find_largest_last_thruster_signal(&code, None), 43210);
     }
 
     #[test]
@@ -107,19 +82,66 @@ mod tests {
         assert_eq!(last_thruster_signal(&code, &phase_setting), 65210);
     }
 
+    #[test]
+    fn part1_on_puzzle_input() {
+        let code = crate::common::read_list_of_numbers::<&str, i64>("data/day07.input", ",");
+        let expected = [0, 1, 2, 3, 4].iter().copied().permutations(5).map(|phase_setting| last_thruster_signal(&code, &phase_setting)).max().unwrap();
+        assert_eq!(find_largest_last_thruster_signal(&code, None), expected);
+        assert_eq!(find_largest_last_thruster_signal(&code, Some(&ThreadPool::new(2))), expected);
+    }
+
+    #[test]
+    fn chain_of_seven_amplifiers() {
+        let code = vec![3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0];
+        let best = best_chain_phase_setting(&code, &[0, 1, 2, 3, 4, 5, 6], Some(&ThreadPool::new(2)));
+        assert_eq!(best, Ok(Some(Solution { phase_setting: vec![6, 5, 4, 3, 2, 1, 0], output: 6_543_210 })));
+        assert_eq!(best_chain_phase_setting(&[3,0,3,0,99], &[0, 1], None), Err(NetworkError::NoOutput));
+    }
+
     #[test]
     fn part2_sample_1() {
         let code = vec![3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5];
         let phase_setting = [9,8,7,6,5];
-        let pool = ThreadPool::new(5);
-        assert_eq!(last_thruster_signal_with_feedback_loop(&code, &phase_setting, &pool), 139_629_729);
+        assert_eq!(last_thruster_signal_with_feedback_loop(&code, &phase_setting), 139_629_729);
+        assert_eq!(find_largest_last_thruster_signal_with_feedback_loop(&code, None), 139_629_729);
     }
 
     #[test]
     fn part2_sample_2() {
         let code = vec![3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10];
         let phase_setting = [9,7,8,5,6];
-        let pool = ThreadPool::new(5);
-        assert_eq!(last_thruster_signal_with_feedback_loop(&code, &phase_setting, &pool), 18_216);
+        assert_eq!(last_thruster_signal_with_feedback_loop(&code, &phase_setting), 18_216);
+    }
+
+    #[test]
+    fn chain_stage_reports() {
+        let code = vec![3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0];
+        let report = chain_report(&code, &[4,3,2,1,0]);
+        assert_eq!(report.signal, Some(43210));
+        assert!(report.all_halted());
+        assert_eq!(report.stages[1], StageReport { name: String::from("B"), inputs: vec![3, 4], outputs: vec![43], instruction_count: 6, termination: Termination::Halted });
+
+        // The first stage waits for a third value nobody can send, the second one waits for the first one.
+        let report = chain_report(&[3,0,3,0,3,0,99], &[1, 2]);
+        assert_eq!(report.stages[0].termination, Termination::Blocked);
+        assert_eq!(report.stages[0].inputs, vec![1, 0]);
+        assert_eq!(report.stages[1].termination, Termination::Blocked);
+        assert_eq!(report.stages[1].inputs, vec![2]);
+        assert_eq!(report.signal, None);
+    }
+
+    #[test]
+    fn feedback_loop_stage_reports() {
+        // Every stage waits for a value after its initial ones.
+        let report = feedback_loop_report(&[3,0,3,0,3,0,99], &[5,6,7,8,9]);
+        assert!(report.stages.iter().all(|stage| stage.termination == Termination::Blocked));
+        assert_eq!(report.stages[0].inputs, vec![5, 0]);
+        assert_eq!(report.signal, None);
+
+        // The first stage halts without sending anything, the next ones see their channel closed in turn.
+        let report = feedback_loop_report(&[3,0,3,0,99], &[5,6,7,8,9]);
+        assert_eq!(report.stages[0].termination, Termination::Halted);
+        assert!(report.stages[1 ..].iter().all(|stage| stage.termination == Termination::ChannelClosed));
+        assert_eq!(report.to_string().lines().nth(1), Some("B: channel closed after 1 instructions, received [6], sent []"));
     }
 }
\ No newline at end of file
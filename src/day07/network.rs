@@ -0,0 +1,375 @@
+// A network of amplifiers running the same program. Each stage first reads its phase setting, then its
+// initial values, then the values sent by its predecessors merged according to its policy. The output of a stage
+// is sent to all its successors. The stages are executed in turn until none of them can progress, the execution
+// is deterministic.
+
+use std::{collections::VecDeque, fmt, fs, io, path::Path, sync::Arc};
+
+use itertools::Itertools;
+use threadpool::ThreadPool;
+
+use super::{intcode, report::{self, Report, StageReport, Termination}, search::{Search, Solution}};
+
+// How the values of several incoming edges are combined.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Merge {
+    Queue, // All values in arrival order.
+    RoundRobin, // One value of each edge in turn, in the order of the edges.
+    Sum, // One value of each edge added, waits for all of them.
+    Max,
+    Min,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stage {
+    pub name: String,
+    pub merge: Merge,
+    pub initial_values: Vec<i64>, // Read after the phase setting.
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Network {
+    pub stages: Vec<Stage>,
+    pub edges: Vec<(usize, usize)>, // Indices of the stages.
+    pub output: usize, // The result is the last value produced by this stage.
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NetworkError {
+    Intcode { stage: usize, error: intcode::Error },
+    NoOutput, // The output stage hasn't produced any value.
+    PhaseSettingLength { expected: usize, actual: usize }, // One phase (or phase domain) per stage is required.
+}
+
+// An invalid description, the lines are numbered from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    UnknownStage { line: usize, name: String },
+    DuplicateStage { line: usize, name: String },
+    UnknownMergePolicy { line: usize, policy: String },
+    InvalidValue { line: usize, value: String }, // An initial value which isn't an integer.
+    InvalidOption { line: usize, option: String },
+    InvalidDeclaration { line: usize, declaration: String },
+    NoOutput, // No output stage declared.
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnknownStage { line, name } => write!(f, "Line {}: unknown stage \"{}\"", line, name),
+            ParseError::DuplicateStage { line, name } => write!(f, "Line {}: stage \"{}\" already declared", line, name),
+            ParseError::UnknownMergePolicy { line, policy } => write!(f, "Line {}: unknown merge policy \"{}\"", line, policy),
+            ParseError::InvalidValue { line, value } => write!(f, "Line {}: invalid value \"{}\"", line, value),
+            ParseError::InvalidOption { line, option } => write!(f, "Line {}: invalid option \"{}\"", line, option),
+            ParseError::InvalidDeclaration { line, declaration } => write!(f, "Line {}: invalid declaration \"{}\"", line, declaration),
+            ParseError::NoOutput => write!(f, "No output stage declared")
+        }
+    }
+}
+
+// Values waiting to be read by a stage, one queue per incoming edge except for 'Merge::Queue'.
+struct Inbox {
+    merge: Merge,
+    queues: Vec<VecDeque<i64>>,
+    next: usize, // Next edge to read with 'Merge::RoundRobin'.
+}
+
+impl Inbox {
+    fn new(merge: Merge, nb_edges: usize) -> Self {
+        Inbox { merge, queues: vec![VecDeque::new(); if merge == Merge::Queue { 1 } else { nb_edges.max(1) }], next: 0 }
+    }
+
+    fn push(&mut self, edge: usize, value: i64) {
+        let queue = if self.merge == Merge::Queue { 0 } else { edge };
+        self.queues[queue].push_back(value)
+    }
+
+    fn pop(&mut self) -> Option<i64> {
+        match self.merge {
+            Merge::Queue => self.queues[0].pop_front(),
+            Merge::RoundRobin => {
+                let value = self.queues[self.next].pop_front()?;
+                self.next = (self.next + 1) % self.queues.len();
+                Some(value)
+            },
+            Merge::Sum | Merge::Max | Merge::Min => {
+                if self.queues.iter().any(VecDeque::is_empty) { return None }
+                let values = self.queues.iter_mut().map(|queue| queue.pop_front().unwrap());
+                match self.merge {
+                    Merge::Sum => Some(values.sum()),
+                    Merge::Max => values.max(),
+                    _ => values.min()
+                }
+            }
+        }
+    }
+}
+
+struct StageIO<'a> {
+    pending: &'a mut VecDeque<i64>, // Phase setting and initial values.
+    inbox: &'a mut Inbox,
+    inputs: &'a mut Vec<i64>,
+    output: Vec<i64>,
+}
+
+impl intcode::IO for StageIO<'_> {
+    fn read(&mut self) -> intcode::Input {
+        match self.pending.pop_front().or_else(|| self.inbox.pop()) {
+            Some(value) => {
+                self.inputs.push(value);
+                intcode::Input::Value(value)
+            },
+            None => intcode::Input::Pending
+        }
+    }
+
+    fn write(&mut self, value: i64) {
+        self.output.push(value)
+    }
+}
+
+impl Network {
+    // 'n' stages, each one sending its output to the next one. The first stage receives 0.
+    // Without any stage there is never a signal.
+    pub fn chain(n: usize) -> Self {
+        let stages = (0 .. n).map(|i| Stage { name: report::stage_name(i), merge: Merge::Queue, initial_values: if i == 0 { vec![0] } else { vec![] } }).collect();
+        Network { stages, edges: (1 .. n).map(|i| (i - 1, i)).collect(), output: n.saturating_sub(1) }
+    }
+
+    // Same as 'chain' with the last stage sending its output to the first one.
+    pub fn ring(n: usize) -> Self {
+        let mut network = Network::chain(n);
+        if n > 0 {
+            network.edges.push((n - 1, 0));
+        }
+        network
+    }
+
+    // One declaration per line, '#' starts a comment, the declarations can come in any order:
+    //  "stage <name> [merge queue|round-robin|sum|max|min] [init <values separated by commas>]"
+    //  "<name> -> <name> [-> <name> ...]": one edge per arrow.
+    //  "output <name>"
+    // The stages are numbered in their order of declaration.
+    pub fn parse(description: &str) -> Result<Self, ParseError> {
+        let mut stages: Vec<Stage> = Vec::new();
+        let mut paths: Vec<(usize, Vec<&str>)> = Vec::new();
+        let mut output = None;
+
+        // The stages first, the edges and the output stage refer to them by name.
+        for (n, line) in description.lines().enumerate() {
+            let line_number = n + 1;
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() { continue }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                ["stage", name, ref options @ ..] => {
+                    if stages.iter().any(|stage| stage.name == name) { return Err(ParseError::DuplicateStage { line: line_number, name: String::from(name) }) }
+                    let mut stage = Stage { name: String::from(name), merge: Merge::Queue, initial_values: Vec::new() };
+                    for option in options.chunks(2) {
+                        match *option {
+                            ["merge", policy] =>
+                                stage.merge =
+                                    match policy {
+                                        "queue" => Merge::Queue,
+                                        "round-robin" => Merge::RoundRobin,
+                                        "sum" => Merge::Sum,
+                                        "max" => Merge::Max,
+                                        "min" => Merge::Min,
+                                        _ => return Err(ParseError::UnknownMergePolicy { line: line_number, policy: String::from(policy) })
+                                    },
+                            ["init", values] =>
+                                stage.initial_values = values.split(',').map(|value| value.parse::<i64>().map_err(|_| ParseError::InvalidValue { line: line_number, value: String::from(value) })).collect::<Result<_, _>>()?,
+                            _ => return Err(ParseError::InvalidOption { line: line_number, option: option.join(" ") })
+                        }
+                    }
+                    stages.push(stage);
+                },
+                ["output", name] => output = Some((line_number, name)),
+                _ if words.len() >= 3 && words.len() % 2 == 1 && words.iter().skip(1).step_by(2).all(|word| *word == "->") =>
+                    paths.push((line_number, words.into_iter().step_by(2).collect())),
+                _ => return Err(ParseError::InvalidDeclaration { line: line_number, declaration: String::from(line) })
+            }
+        }
+
+        let index_of = |line: usize, name: &str| stages.iter().position(|stage| stage.name == name).ok_or_else(|| ParseError::UnknownStage { line, name: String::from(name) });
+        let mut edges: Vec<(usize, usize)> = Vec::new();
+        for (line, names) in paths {
+            let path = names.into_iter().map(|name| index_of(line, name)).collect::<Result<Vec<usize>, ParseError>>()?;
+            edges.extend(path.iter().copied().tuple_windows::<(usize, usize)>());
+        }
+        let output = match output {
+            Some((line, name)) => index_of(line, name)?,
+            None => return Err(ParseError::NoOutput)
+        };
+        Ok(Network { stages, edges, output })
+    }
+
+    pub fn load<P: AsRef<Path>>(file: P) -> io::Result<Self> {
+        Network::parse(&fs::read_to_string(file)?).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))
+    }
+
+    pub fn nb_stages(&self) -> usize { self.stages.len() }
+
+    // Run the network with one phase setting per stage and return the last value produced by the output stage.
+    pub fn run(&self, code: &[i64], phase_setting: &[i64]) -> Result<i64, NetworkError> {
+        let report = self.report(code, phase_setting)?;
+        for (stage, stage_report) in report.stages.iter().enumerate() {
+            if let Termination::Error(error) = stage_report.termination {
+                return Err(NetworkError::Intcode { stage, error })
+            }
+        }
+        report.signal.ok_or(NetworkError::NoOutput)
+    }
+
+    // Run the network until none of the stages can progress, a stage stops at its first error.
+    pub fn report(&self, code: &[i64], phase_setting: &[i64]) -> Result<Report, NetworkError> {
+        let n = self.stages.len();
+        if phase_setting.len() != n {
+            return Err(NetworkError::PhaseSettingLength { expected: n, actual: phase_setting.len() })
+        }
+        let mut machines = vec![intcode::Machine::new(code); n];
+        let mut pending: Vec<VecDeque<i64>> = self.stages.iter().zip(phase_setting).map(|(stage, phase)| std::iter::once(*phase).chain(stage.initial_values.iter().copied()).collect()).collect();
+        let mut reports: Vec<StageReport> =
+            self.stages.iter().map(|stage| StageReport { name: stage.name.clone(), inputs: Vec::new(), outputs: Vec::new(), instruction_count: 0, termination: Termination::Blocked }).collect();
+
+        // For each stage, the index of each incoming edge in its inbox and its destination.
+        let mut inboxes: Vec<Inbox> = (0 .. n).map(|i| Inbox::new(self.stages[i].merge, self.edges.iter().filter(|(_, to)| *to == i).count())).collect();
+        let routes: Vec<Vec<(usize, usize)>> =
+            (0 .. n).map(|i| self.edges.iter().enumerate().filter(|(_, (from, _))| *from == i).map(|(e, (_, to))| (*to, self.edges[.. e].iter().filter(|(_, t)| t == to).count())).collect()).collect();
+
+        loop {
+            let mut progress = false;
+            for i in 0 .. n {
+                if matches!(reports[i].termination, Termination::Halted | Termination::Error(_)) { continue }
+                let before = machines[i].instruction_count();
+                let mut io = StageIO { pending: &mut pending[i], inbox: &mut inboxes[i], inputs: &mut reports[i].inputs, output: Vec::new() };
+                let termination = Termination::from_result(machines[i].run(&mut io));
+                progress |= machines[i].instruction_count() != before;
+
+                for value in io.output {
+                    for (to, edge) in routes[i].iter() {
+                        inboxes[*to].push(*edge, value);
+                    }
+                    reports[i].outputs.push(value);
+                }
+                reports[i].termination = termination;
+                reports[i].instruction_count = machines[i].instruction_count();
+            }
+            if !progress { break }
+        }
+
+        // A stage waiting for values from stages which have all terminated will never receive anything.
+        loop {
+            let closed: Vec<usize> =
+                (0 .. n).filter(
+                    |i| reports[*i].termination == Termination::Blocked && {
+                        let mut senders = self.edges.iter().filter(|(_, to)| to == i).map(|(from, _)| &reports[*from].termination).peekable();
+                        senders.peek().is_some() && senders.all(|termination| *termination != Termination::Blocked)
+                    }
+                ).collect();
+            if closed.is_empty() { break }
+            for i in closed {
+                reports[i].termination = Termination::ChannelClosed;
+            }
+        }
+
+        let signal = reports.get(self.output).and_then(|report| report.outputs.last().copied());
+        Ok(Report { stages: reports, signal })
+    }
+
+    // The best phase setting among the ones allowed by 'search', 'None' if there isn't any.
+    pub fn best_phase_setting(&self, code: &[i64], search: &Search, pool: Option<&ThreadPool>) -> Result<Option<Solution>, NetworkError> {
+        if search.nb_stages() != self.nb_stages() {
+            return Err(NetworkError::PhaseSettingLength { expected: self.nb_stages(), actual: search.nb_stages() })
+        }
+        let network = Arc::new(self.clone());
+        let code: Arc<[i64]> = Arc::from(code);
+        search.solve(pool, move |phase_setting| network.run(&code, phase_setting))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Read the phase and a value, output their sum.
+    const ADD_PHASE: [i64; 14] = [3,11,3,12,1,11,12,13,4,13,99,0,0,0];
+
+    const PART1_SAMPLE_1: [i64; 17] = [3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0];
+    const PART2_SAMPLE_1: [i64; 29] = [3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5];
+
+    #[test]
+    fn chain_and_ring() {
+        assert_eq!(Network::chain(5).run(&PART1_SAMPLE_1, &[4,3,2,1,0]), Ok(43210));
+        let best = Network::chain(5).best_phase_setting(&PART1_SAMPLE_1, &Search::permutations(&[0,1,2,3,4], 5), None);
+        assert_eq!(best, Ok(Some(Solution { phase_setting: vec![4,3,2,1,0], output: 43210 })));
+        assert_eq!(Network::ring(5).run(&PART2_SAMPLE_1, &[9,8,7,6,5]), Ok(139_629_729));
+        let best = Network::ring(5).best_phase_setting(&PART2_SAMPLE_1, &Search::permutations(&[5,6,7,8,9], 5), Some(&ThreadPool::new(4)));
+        assert_eq!(best, Ok(Some(Solution { phase_setting: vec![9,8,7,6,5], output: 139_629_729 })));
+    }
+
+    #[test]
+    fn parse_description() {
+        let network = Network::parse("stage A init 0 # The first stage.\nstage B\nstage C\nstage D\nstage E\nA -> B -> C -> D -> E -> A\noutput E").unwrap();
+        assert_eq!(network, Network::ring(5));
+
+        // The edges and the output stage can be declared before the stages they refer to.
+        let network = Network::parse("output E\nA -> B -> C -> D -> E -> A\nstage A init 0\nstage B\nstage C\nstage D\nstage E").unwrap();
+        assert_eq!(network, Network::ring(5));
+
+        assert_eq!(Network::parse("stage A\nA -> B\noutput A"), Err(ParseError::UnknownStage { line: 2, name: String::from("B") }));
+        assert_eq!(Network::parse("output B\nstage A"), Err(ParseError::UnknownStage { line: 1, name: String::from("B") }));
+        assert_eq!(Network::parse("stage A\nstage A\noutput A"), Err(ParseError::DuplicateStage { line: 2, name: String::from("A") }));
+        assert_eq!(Network::parse("stage A init 1,x\noutput A"), Err(ParseError::InvalidValue { line: 1, value: String::from("x") }));
+        assert_eq!(Network::parse("stage A\nA ->\noutput A"), Err(ParseError::InvalidDeclaration { line: 2, declaration: String::from("A ->") }));
+        let error = Network::parse("stage A merge first\noutput A").unwrap_err();
+        assert_eq!(error, ParseError::UnknownMergePolicy { line: 1, policy: String::from("first") });
+        assert_eq!(error.to_string(), "Line 1: unknown merge policy \"first\"");
+        assert_eq!(Network::parse("stage A\n"), Err(ParseError::NoOutput));
+    }
+
+    #[test]
+    fn merge_policies() {
+        // A sends its output to B and C (declared after C), both send their output to D.
+        let output = |policy: &str| {
+            let network = Network::parse(&format!("stage A init 0\nstage C\nstage B\nstage D merge {}\nA -> B -> D\nA -> C -> D\noutput D", policy)).unwrap();
+            network.run(&ADD_PHASE, &[1, 100, 10, 1000])
+        };
+        assert_eq!(output("queue"), Ok(1000 + 1 + 100)); // C is executed first.
+        assert_eq!(output("round-robin"), Ok(1000 + 1 + 10));
+        assert_eq!(output("sum"), Ok(1000 + 2 + 10 + 100));
+        assert_eq!(output("max"), Ok(1000 + 1 + 100));
+        assert_eq!(output("min"), Ok(1000 + 1 + 10));
+    }
+
+    #[test]
+    fn errors() {
+        // B never receives a value.
+        let network = Network::parse("stage A init 0\nstage B\noutput B").unwrap();
+        assert_eq!(network.run(&ADD_PHASE, &[1, 2]), Err(NetworkError::NoOutput));
+        assert_eq!(Network::chain(2).run(&[3,0,42], &[1, 2]), Err(NetworkError::Intcode { stage: 0, error: intcode::Error::UnknownOpCode { cursor: 2, op_code: 42 } }));
+
+        assert_eq!(Network::chain(2).report(&ADD_PHASE, &[1]), Err(NetworkError::PhaseSettingLength { expected: 2, actual: 1 }));
+        assert_eq!(Network::ring(2).best_phase_setting(&ADD_PHASE, &Search::permutations(&[0, 1], 3), None), Err(NetworkError::PhaseSettingLength { expected: 2, actual: 3 }));
+
+        // Without any stage.
+        assert_eq!(Network::chain(0).run(&ADD_PHASE, &[]), Err(NetworkError::NoOutput));
+        assert_eq!(Network::ring(0).report(&ADD_PHASE, &[]).map(|report| report.stages.len()), Ok(0));
+    }
+
+    #[test]
+    fn stage_reports() {
+        let network = Network::parse("stage A init 0\nstage B\nstage C\nA -> B\noutput B").unwrap();
+        let report = network.report(&ADD_PHASE, &[1, 2, 3]).unwrap();
+        assert_eq!(report.signal, Some(3));
+        assert_eq!(report.stages[1], StageReport { name: String::from("B"), inputs: vec![2, 1], outputs: vec![3], instruction_count: 5, termination: Termination::Halted });
+        assert_eq!(report.stages[2].termination, Termination::Blocked);
+        assert!(!report.all_halted());
+
+        // A halts without sending anything, B then C wait for a stage which has terminated.
+        let report = Network::chain(3).report(&[3,0,3,0,99], &[1, 2, 3]).unwrap();
+        let terminations: Vec<Termination> = report.stages.iter().map(|stage| stage.termination).collect();
+        assert_eq!(terminations, vec![Termination::Halted, Termination::ChannelClosed, Termination::ChannelClosed]);
+    }
+}
@@ -0,0 +1,96 @@
+// What each stage of a run of amplifiers has received, produced and why it has stopped.
+
+use std::fmt;
+
+use super::intcode;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Termination {
+    Halted,
+    Blocked, // Waiting for a value no stage will ever send.
+    ChannelClosed, // Waiting for a value from a stage that has terminated.
+    Error(intcode::Error),
+}
+
+impl Termination {
+    pub fn from_result(result: Result<intcode::Status, intcode::Error>) -> Self {
+        match result {
+            Ok(intcode::Status::Halted) => Termination::Halted,
+            Ok(intcode::Status::WaitingForInput) => Termination::Blocked,
+            Err(intcode::Error::InputClosed { .. }) => Termination::ChannelClosed,
+            Err(error) => Termination::Error(error)
+        }
+    }
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Termination::Halted => write!(f, "halted"),
+            Termination::Blocked => write!(f, "blocked"),
+            Termination::ChannelClosed => write!(f, "channel closed"),
+            Termination::Error(error) => write!(f, "error {:?}", error)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StageReport {
+    pub name: String,
+    pub inputs: Vec<i64>, // Including the phase setting.
+    pub outputs: Vec<i64>,
+    pub instruction_count: u64,
+    pub termination: Termination,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub stages: Vec<StageReport>,
+    pub signal: Option<i64>, // The last value produced by the output stage.
+}
+
+impl Report {
+    pub fn all_halted(&self) -> bool {
+        self.stages.iter().all(|stage| stage.termination == Termination::Halted)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for stage in self.stages.iter() {
+            writeln!(f, "{}: {} after {} instructions, received {:?}, sent {:?}", stage.name, stage.termination, stage.instruction_count, stage.inputs, stage.outputs)?;
+        }
+        match self.signal {
+            Some(signal) => write!(f, "Signal: {}", signal),
+            None => write!(f, "No signal")
+        }
+    }
+}
+
+// Default name of the stage 'i': A, B, C, ..., Z, AA, AB...
+pub fn stage_name(i: usize) -> String {
+    let mut name = Vec::new();
+    let mut n = i;
+    loop {
+        name.push(b'A' + (n % 26) as u8);
+        if n < 26 { break }
+        n = n / 26 - 1;
+    }
+    name.iter().rev().map(|letter| *letter as char).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stage_names() {
+        assert_eq!(stage_name(0), "A");
+        assert_eq!(stage_name(25), "Z");
+        assert_eq!(stage_name(26), "AA");
+        assert_eq!(stage_name(27), "AB");
+        assert_eq!(stage_name(26 + 26 * 26 - 1), "ZZ");
+        assert_eq!(stage_name(26 + 26 * 26), "AAA");
+        assert_eq!(stage_name(usize::MAX).len(), 14);
+    }
+}
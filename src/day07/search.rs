@@ -0,0 +1,282 @@
+// Search of the best phase setting of an amplifier network. The assignments are explored depth first, a branch is
+// pruned as soon as a value is repeated if repeats are forbidden. For a chain the best completion of each partial
+// state (number of stages, signal and phases used) is memoized: the prefixes reaching the same state are explored
+// once. On a thread pool the first levels are split until there are enough jobs to keep all the threads busy.
+
+use std::{collections::HashMap, sync::{Arc, Mutex, mpsc}};
+
+use threadpool::ThreadPool;
+
+// Minimum number of jobs per thread when the exploration is distributed.
+const JOBS_PER_THREAD: usize = 4;
+
+// Maximum number of memoized states, to bound the memory when the states rarely repeat.
+const MEMO_CAPACITY: usize = 1 << 20;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Goal {
+    Maximize,
+    Minimize,
+}
+
+impl Goal {
+    fn is_better(self, output: i64, best: Option<i64>) -> bool {
+        match (self, best) {
+            (_, None) => true,
+            (Goal::Maximize, Some(best)) => output > best,
+            (Goal::Minimize, Some(best)) => output < best
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Search {
+    pub domains: Vec<Vec<i64>>, // Possible phases of each stage.
+    pub repeats: bool, // The same phase can be given to several stages.
+    pub goal: Goal,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Solution {
+    pub phase_setting: Vec<i64>,
+    pub output: i64,
+}
+
+// Compute the value of a prefix of an assignment from the value of the previous prefix.
+type Step<E> = dyn Fn(i64, &[i64]) -> Result<i64, E> + Send + Sync;
+
+// The best phases after a prefix and the output they give, 'None' if the prefix can't be completed.
+type Completion = Option<(Vec<i64>, i64)>;
+
+// The state of a chain after a prefix: its length, its signal and the mask of the phases used.
+type State = (usize, i64, u64);
+
+struct Memo {
+    phases: Vec<i64>, // Sorted, the bit of a phase in a mask is its index. Empty if repeats are allowed.
+    completions: Mutex<HashMap<State, Completion>>,
+}
+
+impl Memo {
+    // 'None' if there are too many phases to fit in a mask.
+    fn new(search: &Search) -> Option<Self> {
+        let mut phases: Vec<i64> = if search.repeats { Vec::new() } else { search.domains.iter().flatten().copied().collect() };
+        phases.sort_unstable();
+        phases.dedup();
+        if phases.len() > 64 { return None }
+        Some(Memo { phases, completions: Mutex::new(HashMap::new()) })
+    }
+
+    fn state(&self, prefix: &[i64], signal: i64) -> State {
+        let mask = prefix.iter().filter_map(|phase| self.phases.binary_search(phase).ok()).fold(0, |mask, i| mask | 1 << i);
+        (prefix.len(), signal, mask)
+    }
+}
+
+fn solution(prefix: Vec<i64>, completion: Completion) -> Option<Solution> {
+    completion.map(|(phases, output)| Solution { phase_setting: [prefix, phases].concat(), output })
+}
+
+impl Search {
+    // The permutations of the given phases, like in day 7.
+    pub fn permutations(phases: &[i64], nb_stages: usize) -> Self {
+        Search { domains: vec![Vec::from(phases); nb_stages], repeats: false, goal: Goal::Maximize }
+    }
+
+    pub fn nb_stages(&self) -> usize { self.domains.len() }
+
+    // 'evaluate' gives the output of a complete phase setting.
+    // Return 'None' if there is no valid phase setting.
+    pub fn solve<E, F>(&self, pool: Option<&ThreadPool>, evaluate: F) -> Result<Option<Solution>, E>
+    where
+        E: Send + 'static,
+        F: Fn(&[i64]) -> Result<i64, E> + Send + Sync + 'static
+    {
+        let n = self.nb_stages();
+        self.run(pool, 0, Arc::new(move |_, prefix: &[i64]| if prefix.len() == n { evaluate(prefix) } else { Ok(0) }), None)
+    }
+
+    // For a chain of stages: 'amplifier' gives the output of the stage 'i' from its input and its phase, the first
+    // stage receives 'input'. A stage is executed at most once for each state of the chain before it.
+    pub fn solve_chain<E, F>(&self, pool: Option<&ThreadPool>, input: i64, amplifier: F) -> Result<Option<Solution>, E>
+    where
+        E: Send + 'static,
+        F: Fn(usize, i64, i64) -> Result<i64, E> + Send + Sync + 'static
+    {
+        let step = Arc::new(move |signal, prefix: &[i64]| amplifier(prefix.len() - 1, signal, *prefix.last().unwrap()));
+        self.run(pool, input, step, Memo::new(self).map(Arc::new))
+    }
+
+    fn run<E: Send + 'static>(&self, pool: Option<&ThreadPool>, initial: i64, step: Arc<Step<E>>, memo: Option<Arc<Memo>>) -> Result<Option<Solution>, E> {
+        let pool = match pool {
+            Some(pool) if self.nb_stages() > 0 => pool,
+            _ => return Ok(solution(Vec::new(), self.complete(&mut Vec::new(), initial, step.as_ref(), memo.as_deref())?))
+        };
+
+        // Extend the prefixes level by level until there are enough jobs, they stay in the order of the sequential
+        // exploration so the same solution (or error) is returned.
+        let mut jobs: Vec<Result<(Vec<i64>, i64), E>> = vec![Ok((Vec::new(), initial))];
+        for _ in 0 .. self.nb_stages() {
+            if jobs.len() >= JOBS_PER_THREAD * pool.max_count() { break }
+            jobs =
+                jobs.into_iter().flat_map(
+                    |job| match job {
+                        Ok((prefix, value)) =>
+                            self.next_phases(&prefix).map(|phase| { let prefix = [&prefix[..], &[phase]].concat(); step(value, &prefix).map(|value| (prefix, value)) }).collect(),
+                        Err(error) => vec![Err(error)]
+                    }
+                ).collect();
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let mut results: Vec<Option<Result<Option<Solution>, E>>> = Vec::new();
+        for (i, job) in jobs.into_iter().enumerate() {
+            match job {
+                Ok((mut prefix, value)) => {
+                    let (search, step, memo, sender) = (self.clone(), step.clone(), memo.clone(), sender.clone());
+                    pool.execute(
+                        move || {
+                            let result = search.complete(&mut prefix, value, step.as_ref(), memo.as_deref());
+                            sender.send((i, result.map(|completion| solution(prefix, completion)))).unwrap_or_default();
+                        }
+                    );
+                    results.push(None);
+                },
+                Err(error) => results.push(Some(Err(error)))
+            }
+        }
+        drop(sender);
+        for (i, result) in receiver.iter() {
+            results[i] = Some(result);
+        }
+
+        let mut best: Option<Solution> = None;
+        for result in results.into_iter().flatten() {
+            if let Some(solution) = result? {
+                if self.goal.is_better(solution.output, best.as_ref().map(|best| best.output)) {
+                    best = Some(solution);
+                }
+            }
+        }
+        Ok(best)
+    }
+
+    fn next_phases<'a>(&'a self, prefix: &'a [i64]) -> impl Iterator<Item = i64> + 'a {
+        self.domains[prefix.len()].iter().copied().filter(move |phase| self.repeats || !prefix.contains(phase))
+    }
+
+    // The best completion of 'prefix' whose value is 'value'.
+    fn complete<E>(&self, prefix: &mut Vec<i64>, value: i64, step: &Step<E>, memo: Option<&Memo>) -> Result<Completion, E> {
+        if prefix.len() == self.nb_stages() {
+            return Ok(Some((Vec::new(), value)))
+        }
+
+        // With one stage left the state is as cheap to complete again as to memoize.
+        let memo = memo.filter(|_| prefix.len() + 1 < self.nb_stages()).map(|memo| (memo, memo.state(prefix, value)));
+        if let Some((memo, state)) = &memo {
+            if let Some(completion) = memo.completions.lock().unwrap().get(state) {
+                return Ok(completion.clone())
+            }
+        }
+
+        let mut best: Completion = None;
+        let phases: Vec<i64> = self.next_phases(prefix).collect();
+        for phase in phases {
+            prefix.push(phase);
+            let result = step(value, prefix).and_then(|value| self.complete(prefix, value, step, memo.map(|(memo, _)| memo)));
+            prefix.pop();
+            if let Some((phases, output)) = result? {
+                if self.goal.is_better(output, best.as_ref().map(|(_, best)| *best)) {
+                    best = Some(([&[phase], &phases[..]].concat(), output));
+                }
+            }
+        }
+
+        if let Some((memo, state)) = memo {
+            let mut completions = memo.completions.lock().unwrap();
+            if completions.len() < MEMO_CAPACITY {
+                completions.insert(state, best.clone());
+            }
+        }
+        Ok(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{convert::Infallible, sync::atomic::{AtomicUsize, Ordering}};
+
+    use itertools::Itertools;
+
+    fn sum(phase_setting: &[i64]) -> Result<i64, Infallible> { Ok(phase_setting.iter().sum()) }
+
+    #[test]
+    fn repeats_and_goal() {
+        let mut search = Search { domains: vec![vec![1, 2], vec![1, 2]], repeats: false, goal: Goal::Maximize };
+        assert_eq!(search.solve(None, sum), Ok(Some(Solution { phase_setting: vec![1, 2], output: 3 })));
+
+        search.repeats = true;
+        assert_eq!(search.solve(None, sum).unwrap().unwrap().phase_setting, vec![2, 2]);
+
+        search.goal = Goal::Minimize;
+        assert_eq!(search.solve(None, sum).unwrap().unwrap().phase_setting, vec![1, 1]);
+
+        // Not enough phases without repeats.
+        assert_eq!(Search::permutations(&[1, 2], 3).solve(None, sum), Ok(None));
+        assert_eq!(Search::permutations(&[1, 2], 3).solve(Some(&ThreadPool::new(2)), sum), Ok(None));
+    }
+
+    #[test]
+    fn errors_are_returned() {
+        let search = Search::permutations(&[1, 2, 3], 3);
+        assert_eq!(search.solve(None, |phase_setting| if phase_setting[0] == 2 { Err(phase_setting[1]) } else { Ok(0) }), Err(1));
+        assert_eq!(search.solve(Some(&ThreadPool::new(2)), |phase_setting| if phase_setting[0] == 2 { Err(phase_setting[1]) } else { Ok(0) }), Err(1));
+
+        // The first error of the sequential exploration, even if it happens while splitting the jobs.
+        let amplifier = |stage: usize, signal: i64, phase: i64| if phase == 3 && (stage == 0 || stage == 2) { Err((stage, signal)) } else { Ok(signal + phase) };
+        assert_eq!(search.solve_chain(None, 0, amplifier), Err((2, 3)));
+        assert_eq!(search.solve_chain(Some(&ThreadPool::new(1)), 0, amplifier), Err((2, 3)));
+    }
+
+    #[test]
+    fn chain_parallel_and_sequential() {
+        // Concatenate the phases as digits.
+        let amplifier = |_, signal: i64, phase: i64| Ok::<i64, Infallible>(signal * 10 + phase);
+        let search = Search { domains: vec![vec![3, 1, 4], vec![1, 5], vec![9, 2, 6]], repeats: false, goal: Goal::Minimize };
+        let expected = Some(Solution { phase_setting: vec![1, 5, 2], output: 152 });
+        assert_eq!(search.solve_chain(None, 0, amplifier), Ok(expected.clone()));
+        assert_eq!(search.solve_chain(Some(&ThreadPool::new(3)), 0, amplifier), Ok(expected));
+    }
+
+    #[test]
+    fn chain_of_seven_stages() {
+        // Signals colliding often, compared to all the permutations in order.
+        let amplifier = |_, signal: i64, phase: i64| Ok::<i64, Infallible>((signal * 3 + phase * phase) % 101);
+        let phases = [0, 1, 2, 3, 4, 5, 6];
+        let mut expected: Option<Solution> = None;
+        for phase_setting in phases.iter().copied().permutations(7) {
+            let output = phase_setting.iter().fold(0, |signal, phase| amplifier(0, signal, *phase).unwrap());
+            if Goal::Maximize.is_better(output, expected.as_ref().map(|expected| expected.output)) {
+                expected = Some(Solution { phase_setting, output });
+            }
+        }
+
+        let search = Search::permutations(&phases, 7);
+        assert_eq!(search.solve_chain(None, 0, amplifier), Ok(expected.clone()));
+        assert_eq!(search.solve_chain(Some(&ThreadPool::new(3)), 0, amplifier), Ok(expected));
+    }
+
+    #[test]
+    fn memoized_chain_of_ten_stages() {
+        // The signal only depends on the phases used, not on their order: a few thousand executions instead of millions.
+        let nb_executions = Arc::new(AtomicUsize::new(0));
+        let counter = nb_executions.clone();
+        let amplifier = move |_, signal: i64, phase: i64| { counter.fetch_add(1, Ordering::Relaxed); Ok::<i64, Infallible>(signal + phase) };
+
+        let search = Search::permutations(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9], 10);
+        assert_eq!(search.solve_chain(None, 0, amplifier.clone()), Ok(Some(Solution { phase_setting: (0 ..= 9).collect(), output: 45 })));
+        assert!(nb_executions.load(Ordering::Relaxed) < 10 * 1024);
+
+        assert_eq!(search.solve_chain(Some(&ThreadPool::new(4)), 0, amplifier).unwrap().unwrap().output, 45);
+    }
+}
@@ -0,0 +1,193 @@
+// Space Image Format: layers of 'width' x 'height' digits concatenated in a single string.
+
+use std::fmt;
+
+use crate::common::image::Rgb;
+
+pub const TRANSPARENT: u8 = 2;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SifError {
+    InvalidDigit { index: usize, c: char },
+    InvalidSize { width: usize, height: usize }, // A dimension is zero.
+    InvalidLength { len: usize, layer_size: usize }, // No layer or an incomplete last layer.
+    InvalidLayer { layer: usize, len: usize }, // A layer hasn't 'width' x 'height' values.
+    InvalidValue { layer: usize, index: usize, value: u8 }, // Not a digit.
+    InvalidColor(String),
+}
+
+impl fmt::Display for SifError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SifError::InvalidDigit { index, c } => write!(f, "Invalid digit {:?} at position {}", c, index),
+            SifError::InvalidSize { width, height } => write!(f, "Invalid size {}x{}", width, height),
+            SifError::InvalidLength { len, layer_size } => write!(f, "{} digits can't be split into layers of {} digits", len, layer_size),
+            SifError::InvalidLayer { layer, len } => write!(f, "The layer {} has {} values", layer, len),
+            SifError::InvalidValue { layer, index, value } => write!(f, "The value {} of the pixel {} of the layer {} isn't a digit", value, index, layer),
+            SifError::InvalidColor(color) => write!(f, "Invalid color \"{}\"", color)
+        }
+    }
+}
+
+// The digits of a string, the whitespaces at the end are ignored.
+pub fn parse_digits(raw: &str) -> Result<Vec<u8>, SifError> {
+    raw.trim_end().chars().enumerate().map(|(index, c)| c.to_digit(10).map(|digit| digit as u8).ok_or(SifError::InvalidDigit { index, c })).collect()
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LayerStats {
+    pub counts: [usize; 10], // Number of pixels of each digit.
+}
+
+impl LayerStats {
+    pub fn count(&self, digit: u8) -> usize { self.counts[digit as usize] }
+}
+
+// Color of each digit, 'None' for a transparent one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [Option<Rgb>; 10],
+}
+
+impl Palette {
+    // 0: black, 1: white, the other digits are transparent.
+    pub fn standard() -> Self {
+        let mut colors = [None; 10];
+        colors[0] = Some([0, 0, 0]);
+        colors[1] = Some([255, 255, 255]);
+        Palette { colors }
+    }
+
+    // "<digit>=<#rrggbb or transparent>" separated by commas, the digits not given are transparent.
+    pub fn parse(description: &str) -> Result<Self, SifError> {
+        let mut colors = [None; 10];
+        for entry in description.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let invalid = || SifError::InvalidColor(String::from(entry));
+            let (digit, color) = entry.split_once('=').ok_or_else(invalid)?;
+            let digit = digit.trim().parse::<usize>().ok().filter(|digit| *digit < 10).ok_or_else(invalid)?;
+            colors[digit] =
+                match color.trim() {
+                    "transparent" => None,
+                    color => {
+                        let rgb = color.strip_prefix('#').filter(|hex| hex.len() == 6).and_then(|hex| u32::from_str_radix(hex, 16).ok()).ok_or_else(invalid)?;
+                        Some([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8])
+                    }
+                };
+        }
+        Ok(Palette { colors })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub layers: Vec<Vec<u8>>, // The first one is in front.
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, layers: Vec<Vec<u8>>) -> Result<Self, SifError> {
+        if width == 0 || height == 0 {
+            return Err(SifError::InvalidSize { width, height })
+        }
+        for (i, layer) in layers.iter().enumerate() {
+            if layer.len() != width * height {
+                return Err(SifError::InvalidLayer { layer: i, len: layer.len() })
+            }
+            if let Some((index, value)) = layer.iter().enumerate().find(|(_, value)| **value > 9) {
+                return Err(SifError::InvalidValue { layer: i, index, value: *value })
+            }
+        }
+        Ok(Image { width, height, layers })
+    }
+
+    pub fn from_digits(digits: &[u8], width: usize, height: usize) -> Result<Self, SifError> {
+        let layer_size = width * height;
+        if layer_size == 0 {
+            return Err(SifError::InvalidSize { width, height })
+        }
+        if digits.is_empty() || digits.len() % layer_size != 0 {
+            return Err(SifError::InvalidLength { len: digits.len(), layer_size })
+        }
+        Image::new(width, height, digits.chunks(layer_size).map(Vec::from).collect())
+    }
+
+    pub fn decode(raw: &str, width: usize, height: usize) -> Result<Self, SifError> {
+        Image::from_digits(&parse_digits(raw)?, width, height)
+    }
+
+    pub fn encode(&self) -> String {
+        self.layers.iter().flatten().map(|digit| (b'0' + digit) as char).collect()
+    }
+
+    pub fn stats(&self) -> Vec<LayerStats> {
+        self.layers.iter().map(|layer| {
+            let mut stats = LayerStats::default();
+            for digit in layer {
+                stats.counts[*digit as usize] += 1;
+            }
+            stats
+        }).collect()
+    }
+
+    // The number of 1 digits multiplied by the number of 2 digits of the layer with the fewest 0 digits.
+    pub fn checksum(&self) -> usize {
+        self.stats().iter().min_by_key(|stats| stats.count(0)).map_or(0, |stats| stats.count(1) * stats.count(2))
+    }
+
+    // Each pixel has the value of the first layer where it isn't 'transparent', 'transparent' if there is none.
+    pub fn flatten(&self, transparent: u8) -> Vec<u8> {
+        (0 .. self.width * self.height).map(|i| self.layers.iter().map(|layer| layer[i]).find(|digit| *digit != transparent).unwrap_or(transparent)).collect()
+    }
+
+    // Each pixel has the color of the first layer where it isn't transparent, 'background' if there is none.
+    pub fn flatten_with_palette(&self, palette: &Palette, background: Rgb) -> Vec<Rgb> {
+        (0 .. self.width * self.height).map(|i| self.layers.iter().find_map(|layer| palette.colors[layer[i] as usize]).unwrap_or(background)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::image;
+
+    #[test]
+    fn decode_and_encode() {
+        let image = Image::decode("0222112222120000\n", 2, 2).unwrap();
+        assert_eq!(image.layers, vec![vec![0, 2, 2, 2], vec![1, 1, 2, 2], vec![2, 2, 1, 2], vec![0, 0, 0, 0]]);
+        assert_eq!(image.encode(), "0222112222120000");
+
+        assert_eq!(Image::decode("12a4", 2, 1), Err(SifError::InvalidDigit { index: 2, c: 'a' }));
+        assert_eq!(Image::decode("12345", 2, 2), Err(SifError::InvalidLength { len: 5, layer_size: 4 }));
+        assert_eq!(Image::decode("", 2, 2), Err(SifError::InvalidLength { len: 0, layer_size: 4 }));
+        assert_eq!(Image::decode("1234", 0, 2), Err(SifError::InvalidSize { width: 0, height: 2 }));
+        assert_eq!(Image::new(1, 2, vec![vec![1, 2], vec![3]]), Err(SifError::InvalidLayer { layer: 1, len: 1 }));
+        assert_eq!(Image::new(1, 1, vec![vec![12]]).map_err(|error| error.to_string()), Err(String::from("The value 12 of the pixel 0 of the layer 0 isn't a digit")));
+    }
+
+    #[test]
+    fn stats() {
+        let image = Image::decode("123456789012", 3, 2).unwrap();
+        let stats = image.stats();
+        assert_eq!((stats[0].count(1), stats[0].count(0), stats[1].count(0)), (1, 0, 1));
+        assert_eq!(image.checksum(), 1);
+    }
+
+    #[test]
+    fn flatten() {
+        let image = Image::decode("0222112222120000", 2, 2).unwrap();
+        assert_eq!(image.flatten(TRANSPARENT), vec![0, 1, 1, 0]);
+        assert_eq!(image.flatten(0), vec![1, 2, 2, 2]);
+        assert_eq!(Image::decode("2", 1, 1).unwrap().flatten(TRANSPARENT), vec![2]);
+
+        let palette = Palette::parse("0=#000000, 1=#ffffff, 2=transparent, 3=#ff0000").unwrap();
+        assert_eq!(palette.colors[3], Some([255, 0, 0]));
+        assert_eq!(Palette::parse("1=#fff"), Err(SifError::InvalidColor(String::from("1=#fff"))));
+
+        // The digit 9 has no color so it is transparent, the last pixel is transparent in every layer.
+        let image = Image::decode("02923339", 2, 2).unwrap();
+        let pixels = image.flatten_with_palette(&palette, [0, 0, 255]);
+        assert_eq!(pixels, vec![[0, 0, 0], [255, 0, 0], [255, 0, 0], [0, 0, 255]]);
+        assert!(image::rgb_to_ppm(&pixels, image.width).unwrap().starts_with(b"P6\n2 2\n255\n"));
+    }
+}
@@ -1,66 +1,324 @@
-use std::collections::HashMap;
+use std::{collections::{HashMap, HashSet}, convert::TryFrom};
 
 use super::intcode;
 
-enum NextCommand {
-    Paint,
-    Turn
+pub mod replay;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Heading {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Heading {
+    const ALL: [Heading; 4] = [Heading::Up, Heading::Right, Heading::Down, Heading::Left];
+
+    pub fn turn_right(self) -> Self { Heading::ALL[(self as usize + 1) % 4] }
+
+    pub fn turn_left(self) -> Self { Heading::ALL[(self as usize + 3) % 4] }
+
+    pub fn reverse(self) -> Self { Heading::ALL[(self as usize + 2) % 4] }
+
+    pub fn arrow(self) -> char {
+        match self {
+            Heading::Up => '^',
+            Heading::Right => '>',
+            Heading::Down => 'v',
+            Heading::Left => '<'
+        }
+    }
+
+    fn from_arrow(c: char) -> Option<Self> {
+        Heading::ALL.iter().copied().find(|heading| heading.arrow() == c)
+    }
+
+    // The Y axis is up.
+    fn next(self, (x, y): (i32, i32)) -> (i32, i32) {
+        match self {
+            Heading::Up    => (x    , y + 1),
+            Heading::Right => (x + 1, y    ),
+            Heading::Down  => (x    , y - 1),
+            Heading::Left  => (x - 1, y    )
+        }
+    }
+}
+
+// How the second value of a command changes the heading of the robot.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Steering {
+    LeftRight, // 0: turn left, 1: turn right, like in day 11.
+    LeftRightUTurn, // 0: turn left, 1: turn right, 2: U-turn.
+    Absolute, // 0: up, 1: right, 2: down, 3: left.
+}
+
+impl Steering {
+    fn apply(self, heading: Heading, value: i64) -> Option<Heading> {
+        match (self, value) {
+            (Steering::LeftRight | Steering::LeftRightUTurn, 0) => Some(heading.turn_left()),
+            (Steering::LeftRight | Steering::LeftRightUTurn, 1) => Some(heading.turn_right()),
+            (Steering::LeftRightUTurn, 2) => Some(heading.reverse()),
+            (Steering::Absolute, 0 ..= 3) => Some(Heading::ALL[value as usize]),
+            _ => None
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Color {
+    pub value: i64, // As read and painted by the program.
+    pub glyph: char,
+    pub rgb: [u8; 3],
+}
+
+// The first color is the one of the panels never painted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: Vec<Color>,
+}
+
+impl Palette {
+    pub fn black_and_white() -> Self {
+        Palette { colors: vec![Color { value: 0, glyph: '.', rgb: [0, 0, 0] }, Color { value: 1, glyph: '#', rgb: [255, 255, 255] }] }
+    }
+
+    // One color per line: "<value> <glyph> #<rrggbb>".
+    pub fn parse(description: &str) -> Result<Self, String> {
+        let mut colors = Vec::new();
+        for (n, line) in description.lines().map(str::trim).enumerate().filter(|(_, line)| !line.is_empty()) {
+            let error = |message: &str| format!("Line {}: {}", n + 1, message);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[..] {
+                [value, glyph, rgb] => {
+                    let value = value.parse::<i64>().map_err(|e| error(&e.to_string()))?;
+                    let mut chars = glyph.chars();
+                    let glyph = match (chars.next(), chars.next()) { (Some(c), None) => c, _ => return Err(error("the glyph must be a single character")) };
+                    let rgb = u32::from_str_radix(rgb.strip_prefix('#').unwrap_or(""), 16).ok().filter(|_| rgb.len() == 7).ok_or_else(|| error("invalid color, expected #rrggbb"))?;
+                    colors.push(Color { value, glyph, rgb: [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8] });
+                },
+                _ => return Err(error("expected \"<value> <glyph> #<rrggbb>\""))
+            }
+        }
+        if colors.is_empty() {
+            return Err(String::from("Empty palette"))
+        }
+        Ok(Palette { colors })
+    }
+
+    pub fn background(&self) -> i64 { self.colors[0].value }
+
+    pub fn get(&self, value: i64) -> Option<&Color> {
+        self.colors.iter().find(|color| color.value == value)
+    }
+
+    pub fn with_glyph(&self, glyph: char) -> Option<&Color> {
+        self.colors.iter().find(|color| color.glyph == glyph)
+    }
+}
+
+// Panels painted before the robot starts and its initial position and heading.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hull {
+    pub panels: HashMap<(i32, i32), i64>,
+    pub start: (i32, i32),
+    pub heading: Heading,
+}
+
+impl Hull {
+    pub fn new() -> Self {
+        Hull { panels: HashMap::new(), start: (0, 0), heading: Heading::Up }
+    }
+
+    // One character per panel with the glyphs of the palette, the first line is the top one.
+    // The robot is given by an arrow ('^', '>', 'v' or '<') on a panel of the background color,
+    // at the top left panel heading up if there is none.
+    pub fn parse(map: &str, palette: &Palette) -> Result<Self, String> {
+        let mut hull = Hull::new();
+        let mut robot = None;
+        for (row, line) in map.lines().enumerate() {
+            for (column, c) in line.trim_end().chars().enumerate() {
+                let position = (column as i32, -(row as i32));
+                if let Some(heading) = Heading::from_arrow(c) {
+                    if robot.is_some() { return Err(format!("Line {}: more than one robot", row + 1)) }
+                    robot = Some((position, heading));
+                    continue
+                }
+                let color = palette.with_glyph(c).ok_or_else(|| format!("Line {}: unknown glyph '{}'", row + 1, c))?;
+                hull.panels.insert(position, color.value);
+            }
+        }
+        if let Some((start, heading)) = robot {
+            hull.start = start;
+            hull.heading = heading;
+        }
+        Ok(hull)
+    }
+
+    // A layer of colors like the ones of day 8, the robot starts on the given pixel heading up.
+    pub fn from_layer(layer: &[u8], width: usize, start: (usize, usize)) -> Result<Self, String> {
+        if width == 0 {
+            return Err(String::from("The width of the layer must be greater than 0"))
+        }
+        let panels = layer.iter().enumerate().map(|(i, color)| (((i % width) as i32, -((i / width) as i32)), *color as i64)).collect();
+        Ok(Hull { panels, start: (start.0 as i32, -(start.1 as i32)), heading: Heading::Up })
+    }
 }
 
-struct Robot {
-    next_command: NextCommand,
-    current_pos: (i32, i32),
-    current_dir: i32, // 0: up, 1: right, 2: down, 3: left.
-    panels: HashMap<(i32, i32), i64>
+impl Default for Hull {
+    fn default() -> Self { Hull::new() }
+}
+
+// A frame of the output: the color to paint the current panel and how to turn.
+pub struct Command {
+    pub color: i64,
+    pub turn: i64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CommandError {
+    InvalidLength(usize), // A command has exactly 2 values.
+}
+
+impl intcode::framing::Decode for Command {
+    type Error = CommandError;
+
+    fn decode(values: &[i64]) -> Result<Self, CommandError> {
+        match *values {
+            [color, turn] => Ok(Command { color, turn }),
+            _ => Err(CommandError::InvalidLength(values.len()))
+        }
+    }
+}
+
+// What the robot has done for one command.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub position: (i32, i32), // Of the painted panel.
+    pub color: i64,
+    pub heading: Heading, // After the turn.
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RobotError {
+    UnknownColor { step: usize, value: i64 },
+    InvalidTurn { step: usize, value: i64 },
+}
+
+pub struct Robot {
+    pub palette: Palette,
+    pub steering: Steering,
+    pub position: (i32, i32),
+    pub heading: Heading,
+    pub panels: HashMap<(i32, i32), i64>,
+    pub history: Vec<Step>,
+    error: Option<RobotError>,
 }
 
 impl Robot {
-    fn new() -> Self {
+    pub fn new(palette: Palette, steering: Steering, hull: Hull) -> Self {
         Robot {
-            next_command: NextCommand::Paint,
-            current_pos: (0, 0),
-            current_dir: 0,
-            panels: HashMap::new()
+            palette,
+            steering,
+            position: hull.start,
+            heading: hull.heading,
+            panels: hull.panels,
+            history: Vec::new(),
+            error: None
         }
     }
+
+    pub fn color(&self, position: (i32, i32)) -> i64 {
+        *self.panels.get(&position).unwrap_or(&self.palette.background())
+    }
+
+    // Panels painted at least once.
+    pub fn painted_panels(&self) -> HashSet<(i32, i32)> {
+        self.history.iter().map(|step| step.position).collect()
+    }
+
+    pub fn error(&self) -> Option<RobotError> { self.error }
 }
 
-impl intcode::IO for Robot {
-    fn read(&mut self) -> i64 {
-        *self.panels.get(&self.current_pos).unwrap_or(&0)
+impl Default for Robot {
+    fn default() -> Self { Robot::new(Palette::black_and_white(), Steering::LeftRight, Hull::new()) }
+}
+
+impl intcode::framing::Device for Robot {
+    type Frame = Command;
+
+    fn read(&mut self) -> intcode::Input {
+        intcode::Input::Value(self.color(self.position))
     }
 
-    fn write(&mut self, value: i64) {
-        self.next_command =
-            match self.next_command {
-                NextCommand::Paint => { self.panels.insert(self.current_pos, value); NextCommand::Turn },
-                NextCommand::Turn => {
-                    self.current_dir = (self.current_dir + if value == 0 /* Turn left. */ { 3 } else /* Turn right. */ { 1 }) % 4;
-                    let (x, y) = self.current_pos;
-                    self.current_pos =
-                        match self.current_dir {
-                            0     => (x    , y + 1),
-                            1     => (x + 1, y    ),
-                            2     => (x    , y - 1),
-                            3 | _ => (x - 1, y    )
-                        };
-                    NextCommand::Paint
-                }
+    fn receive(&mut self, command: Command) {
+        let step = self.history.len();
+        if self.palette.get(command.color).is_none() {
+            self.error = Some(RobotError::UnknownColor { step, value: command.color });
+            return
+        }
+        let heading = match self.steering.apply(self.heading, command.turn) {
+            Some(heading) => heading,
+            None => {
+                self.error = Some(RobotError::InvalidTurn { step, value: command.turn });
+                return
             }
+        };
+
+        self.panels.insert(self.position, command.color);
+        self.history.push(Step { position: self.position, color: command.color, heading });
+        self.heading = heading;
+        self.position = heading.next(self.position);
+    }
+
+    fn halt(&self) -> bool { self.error.is_some() }
+}
+
+const FRAMING: intcode::framing::Framing = intcode::framing::Framing::fixed(2);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PaintError {
+    Intcode(intcode::Error),
+    Robot(RobotError),
+    IncompleteCommand(Vec<i64>), // The program has halted after the color of a command.
+    InvalidCommand(CommandError),
+}
+
+pub fn paint(code: &[i64], robot: Robot) -> Result<Robot, PaintError> {
+    let mut robot = intcode::framing::Framed::new(robot, FRAMING);
+    intcode::execute_op_code_with_custom_io(code, &mut robot).map_err(PaintError::Intcode)?;
+    match robot.into_result() {
+        Ok(robot) => match robot.error {
+            Some(error) => Err(PaintError::Robot(error)),
+            None => Ok(robot)
+        },
+        Err(intcode::framing::FrameError::Incomplete { values }) => Err(PaintError::IncompleteCommand(values)),
+        Err(intcode::framing::FrameError::Decode { error, .. }) => Err(PaintError::InvalidCommand(error))
     }
 }
 
+// Same as 'paint' but the execution is also recorded.
+pub fn record_painting(code: &[i64], robot: Robot) -> intcode::record::Session {
+    let mut robot = intcode::framing::Framed::new(robot, FRAMING);
+    let mut session = intcode::record::Session::new();
+    intcode::record::record(&mut intcode::Machine::new(code), &mut robot, &mut session).unwrap();
+    session
+}
+
 pub fn run_robot(code: &[i64], initial_value: i64) -> HashMap<(i32, i32), i64> {
-    let mut robot = Robot::new();
+    let mut hull = Hull::new();
     if initial_value != 0 {
-        robot.panels.insert((0, 0), initial_value);
+        hull.panels.insert((0, 0), initial_value);
     }
-
-    intcode::execute_op_code_with_custom_io(code, &mut robot);
-    robot.panels
+    paint(code, Robot::new(Palette::black_and_white(), Steering::LeftRight, hull)).unwrap().panels
 }
 
-pub fn panels_to_layer(panels: &HashMap<(i32, i32), i64>) -> (Vec<u8>, usize) {
+// Fail if a color doesn't fit in a byte. The layer is empty if there is no panel.
+pub fn panels_to_layer(panels: &HashMap<(i32, i32), i64>) -> Result<(Vec<u8>, usize), String> {
+    if panels.is_empty() {
+        return Ok((Vec::new(), 0))
+    }
+
     let coordinates: Vec<&(i32, i32)> = panels.keys().collect();
     let min_x = coordinates.iter().min_by_key(|(x, _)| x).unwrap().0;
     let max_x = coordinates.iter().max_by_key(|(x, _)| x).unwrap().0;
@@ -70,17 +328,17 @@ pub fn panels_to_layer(panels: &HashMap<(i32, i32), i64>) -> (Vec<u8>, usize) {
     let width = (max_x - min_x) as usize + 1;
     let height = (max_y - min_y) as usize + 1;
 
-    let mut layer = Vec::new();
-    layer.resize(width * height, 0);
+    let mut layer = vec![0; width * height];
 
     for x in min_x ..= max_x {
         for y in min_y ..= max_y {
             let pos = (x - min_x) + ((height as i32 - y + min_y - 1) * width as i32); // Y axis is down.
-            layer[pos as usize] = *panels.get(&(x, y)).unwrap_or(&0) as u8;
+            let color = *panels.get(&(x, y)).unwrap_or(&0);
+            layer[pos as usize] = u8::try_from(color).map_err(|_| format!("The color {} of the panel ({}, {}) doesn't fit in a layer", color, x, y))?;
         }
     }
 
-    (layer, width)
+    Ok((layer, width))
 }
 
 #[cfg(test)]
@@ -90,7 +348,7 @@ mod tests {
 
     #[test]
     fn part1() {
-        let mut robot = Robot::new();
+        let mut robot = intcode::framing::Framed::new(Robot::default(), FRAMING);
         robot.write(1); // Paint white.
         robot.write(0); // Turn left.
 
@@ -112,6 +370,50 @@ mod tests {
         robot.write(1); // Paint white.
         robot.write(0); // Turn left.
 
-        assert_eq!(robot.panels.len(), 6);
+        assert_eq!(robot.device.panels.len(), 6);
+        assert_eq!(robot.device.painted_panels().len(), 6);
+        assert_eq!(robot.device.history[4], Step { position: (0, 0), color: 0, heading: Heading::Right });
+    }
+
+    #[test]
+    fn palette_and_hull() {
+        let palette = Palette::parse("0 . #000000\n1 # #ffffff\n2 r #ff0000").unwrap();
+        assert_eq!(palette.get(2), Some(&Color { value: 2, glyph: 'r', rgb: [255, 0, 0] }));
+        assert_eq!(Palette::parse("0 . #00000"), Err(String::from("Line 1: invalid color, expected #rrggbb")));
+
+        let hull = Hull::parse("#r.\n.<#", &palette).unwrap();
+        assert_eq!((hull.start, hull.heading), ((1, -1), Heading::Left));
+        assert_eq!(hull.panels.get(&(1, 0)), Some(&2));
+        assert_eq!(hull.panels.get(&(1, -1)), None);
+        assert_eq!(Hull::parse("#x", &palette), Err(String::from("Line 1: unknown glyph 'x'")));
+
+        assert_eq!(Hull::from_layer(&[0, 1], 0, (0, 0)), Err(String::from("The width of the layer must be greater than 0")));
+        assert_eq!(Hull::from_layer(&[0, 1, 1, 0], 2, (0, 1)), Ok(Hull { panels: [((0, 0), 0), ((1, 0), 1), ((0, -1), 1), ((1, -1), 0)].iter().copied().collect(), start: (0, -1), heading: Heading::Up }));
+    }
+
+    #[test]
+    fn steering() {
+        // Read the panel, paint it with 2 then make a U-turn, paint the next one with the color read then turn right.
+        let code = [3,100,104,2,104,2,4,100,104,1,99];
+        let robot = Robot::new(Palette::parse("0 . #000000\n1 # #ffffff\n2 r #ff0000").unwrap(), Steering::LeftRightUTurn, Hull::new());
+        let robot = paint(&code, robot).unwrap();
+        assert_eq!(robot.history, vec![Step { position: (0, 0), color: 2, heading: Heading::Down }, Step { position: (0, -1), color: 0, heading: Heading::Left }]);
+        assert_eq!(robot.position, (-1, -1));
+
+        let robot = Robot::new(Palette::black_and_white(), Steering::Absolute, Hull::new());
+        assert_eq!(paint(&[104,1,104,4,99], robot).err(), Some(PaintError::Robot(RobotError::InvalidTurn { step: 0, value: 4 })));
+        assert_eq!(paint(&[104,2,104,0,99], Robot::default()).err(), Some(PaintError::Robot(RobotError::UnknownColor { step: 0, value: 2 })));
+        assert_eq!(paint(&[104,1,99], Robot::default()).err(), Some(PaintError::IncompleteCommand(vec![1])));
+        assert_eq!(<Command as intcode::framing::Decode>::decode(&[1]).err(), Some(CommandError::InvalidLength(1)));
+    }
+
+    #[test]
+    fn layer_of_panels() {
+        let panels: HashMap<(i32, i32), i64> = [((0, 0), 1), ((1, -1), 1)].iter().copied().collect();
+        assert_eq!(panels_to_layer(&panels), Ok((vec![1, 0, 0, 1], 2)));
+        assert_eq!(panels_to_layer(&HashMap::new()), Ok((Vec::new(), 0)));
+
+        let panels: HashMap<(i32, i32), i64> = [((0, 0), 1), ((1, 0), 300)].iter().copied().collect();
+        assert_eq!(panels_to_layer(&panels), Err(String::from("The color 300 of the panel (1, 0) doesn't fit in a layer")));
     }
 }
\ No newline at end of file
@@ -0,0 +1,176 @@
+// Replay of the steps of a painting robot, frame by frame in a terminal or as PPM images.
+
+use std::{collections::HashMap, fmt, fs, io::{self, Write}, path::Path, thread, time::Duration};
+
+use crate::common::image;
+use super::{Heading, Hull, Palette, Robot, Step};
+
+// The state of the hull after some steps.
+pub struct Frame<'a> {
+    pub number: usize,
+    pub nb_steps: usize,
+    pub panels: &'a HashMap<(i32, i32), i64>,
+    pub position: (i32, i32),
+    pub heading: Heading,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Summary {
+    pub nb_steps: usize,
+    pub painted: usize, // Panels painted at least once.
+    pub repainted: usize, // Panels painted more than once.
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} steps, {} panels painted at least once, {} repainted", self.nb_steps, self.painted, self.repainted)
+    }
+}
+
+pub struct Replay {
+    palette: Palette,
+    hull: Hull,
+    steps: Vec<Step>,
+    min: (i32, i32),
+    max: (i32, i32),
+}
+
+impl Replay {
+    // 'hull' is the one the robot has started with.
+    pub fn new(hull: &Hull, robot: &Robot) -> Self {
+        let positions =
+            hull.panels.keys().copied()
+                .chain(std::iter::once(hull.start))
+                .chain(robot.history.iter().flat_map(|step| vec![step.position, step.heading.next(step.position)]));
+        let (mut min, mut max) = (hull.start, hull.start);
+        for (x, y) in positions {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        Replay { palette: robot.palette.clone(), hull: hull.clone(), steps: robot.history.clone(), min, max }
+    }
+
+    pub fn width(&self) -> usize { (self.max.0 - self.min.0) as usize + 1 }
+
+    pub fn height(&self) -> usize { (self.max.1 - self.min.1) as usize + 1 }
+
+    // Call 'f' with the initial state then after each 'steps_per_frame' steps, the last frame is always the final state.
+    pub fn for_each_frame<E>(&self, steps_per_frame: usize, f: &mut dyn FnMut(&Frame) -> Result<(), E>) -> Result<(), E> {
+        let steps_per_frame = steps_per_frame.max(1);
+        let mut panels = self.hull.panels.clone();
+        f(&Frame { number: 0, nb_steps: 0, panels: &panels, position: self.hull.start, heading: self.hull.heading })?;
+
+        let mut number = 0;
+        for (i, step) in self.steps.iter().enumerate() {
+            panels.insert(step.position, step.color);
+            let nb_steps = i + 1;
+            if nb_steps % steps_per_frame == 0 || nb_steps == self.steps.len() {
+                number += 1;
+                f(&Frame { number, nb_steps, panels: &panels, position: step.heading.next(step.position), heading: step.heading })?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn nb_frames(&self, steps_per_frame: usize) -> usize {
+        let steps_per_frame = steps_per_frame.max(1);
+        1 + (self.steps.len() + steps_per_frame - 1) / steps_per_frame
+    }
+
+    fn color(&self, frame: &Frame, position: (i32, i32)) -> Option<&super::Color> {
+        self.palette.get(*frame.panels.get(&position).unwrap_or(&self.palette.background()))
+    }
+
+    // One line per row with the glyphs of the palette ('?' for an unknown color), the robot is shown by its heading.
+    pub fn render_frame(&self, frame: &Frame) -> String {
+        let mut lines = Vec::new();
+        for y in (self.min.1 ..= self.max.1).rev() {
+            lines.push((self.min.0 ..= self.max.0).map(|x| {
+                if (x, y) == frame.position { frame.heading.arrow() } else { self.color(frame, (x, y)).map_or('?', |color| color.glyph) }
+            }).collect::<String>());
+        }
+        lines.join("\n")
+    }
+
+    // A binary PPM image with 'cell_size' pixels per panel, the panel under the robot has its color inverted.
+    pub fn frame_to_ppm(&self, frame: &Frame, cell_size: usize) -> Vec<u8> {
+        let mut cells: Vec<image::Rgb> = Vec::with_capacity(self.width() * self.height());
+        for y in (self.min.1 ..= self.max.1).rev() {
+            for x in self.min.0 ..= self.max.0 {
+                let rgb = self.color(frame, (x, y)).map_or([0, 0, 0], |color| color.rgb);
+                cells.push(if (x, y) == frame.position { rgb.map(|c| 255 - c) } else { rgb });
+            }
+        }
+        let (pixels, width) = image::scale(&cells, self.width(), cell_size.max(1)).unwrap();
+        image::rgb_to_ppm(&pixels, width).unwrap()
+    }
+
+    // Show the frames in a terminal, 'delay' after each one.
+    pub fn play(&self, out: &mut dyn Write, steps_per_frame: usize, delay: Duration) -> io::Result<()> {
+        self.for_each_frame(steps_per_frame, &mut |frame| {
+            write!(out, "\x1b[H\x1b[2J{}\nStep {}/{}\n", self.render_frame(frame), frame.nb_steps, self.steps.len())?;
+            out.flush()?;
+            thread::sleep(delay);
+            Ok(())
+        })
+    }
+
+    // Write the frames in 'directory' as "frame_00000.ppm", "frame_00001.ppm"... and return their number.
+    pub fn export_ppm<P: AsRef<Path>>(&self, directory: P, steps_per_frame: usize, cell_size: usize) -> io::Result<usize> {
+        fs::create_dir_all(&directory)?;
+        let mut nb_frames = 0;
+        self.for_each_frame(steps_per_frame, &mut |frame| {
+            nb_frames += 1;
+            fs::write(directory.as_ref().join(format!("frame_{:05}.ppm", frame.number)), self.frame_to_ppm(frame, cell_size))
+        })?;
+        Ok(nb_frames)
+    }
+
+    pub fn summary(&self) -> Summary {
+        let mut nb_paintings: HashMap<(i32, i32), usize> = HashMap::new();
+        for step in self.steps.iter() {
+            *nb_paintings.entry(step.position).or_insert(0) += 1;
+        }
+        Summary { nb_steps: self.steps.len(), painted: nb_paintings.len(), repainted: nb_paintings.values().filter(|n| **n > 1).count() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Steering, paint};
+
+    // Paint white and turn left four times: the robot goes around a square and repaints the first panel.
+    const SQUARE: [i64; 21] = [104,1,104,0,104,1,104,0,104,1,104,0,104,1,104,0,104,1,104,0,99];
+
+    fn replay() -> Replay {
+        let robot = paint(&SQUARE, Robot::new(Palette::black_and_white(), Steering::LeftRight, Hull::new())).unwrap();
+        Replay::new(&Hull::new(), &robot)
+    }
+
+    #[test]
+    fn frames_and_summary() {
+        let replay = replay();
+        assert_eq!((replay.width(), replay.height()), (2, 2));
+        assert_eq!(replay.summary(), Summary { nb_steps: 5, painted: 4, repainted: 1 });
+        assert_eq!(replay.summary().to_string(), "5 steps, 4 panels painted at least once, 1 repainted");
+        assert_eq!(replay.nb_frames(2), 4);
+
+        let mut frames = Vec::new();
+        replay.for_each_frame(2, &mut |frame| { frames.push((frame.nb_steps, replay.render_frame(frame))); Ok::<(), ()>(()) }).unwrap();
+        assert_eq!(frames, vec![(0, String::from(".^\n..")), (2, String::from("##\nv.")), (4, String::from("#^\n##")), (5, String::from("<#\n##"))]);
+    }
+
+    #[test]
+    fn ppm_frames() {
+        let replay = replay();
+        let mut images = Vec::new();
+        replay.for_each_frame(5, &mut |frame| { images.push(replay.frame_to_ppm(frame, 2)); Ok::<(), ()>(()) }).unwrap();
+
+        let header = b"P6\n4 4\n255\n";
+        assert!(images[1].starts_with(header));
+        assert_eq!(images[1].len(), header.len() + 4 * 4 * 3);
+        // The robot is on the top left panel painted white.
+        assert_eq!(&images[1][header.len() ..][.. 4 * 3], &[0, 0, 0, 0, 0, 0, 255, 255, 255, 255, 255, 255]);
+    }
+}
@@ -1,72 +1,45 @@
-use std::{cmp::Ordering, convert::TryFrom};
+use std::cmp::Ordering;
 
-use itertools::Itertools;
-use num_enum::TryFromPrimitive;
+use super::intcode::{self, framing::{self, Framed}, screen::{self, Arcade, Frame, Screen, Segment, Tile}};
 
-use super::intcode;
-
-// Not neccesary, to try to parse enum in Rust.
-#[derive(Debug, Eq, PartialEq, TryFromPrimitive)]
-#[repr(u8)]
-enum Tile {
-    Empty,
-    Wall,
-    Block,
-    Paddle,
-    Ball,
-}
+pub mod play;
 
 pub fn count_nb_block(code: &[i64]) -> i32 {
     let output = intcode::execute_op_code(code, &[]);
+    let mut screen = Screen::new();
+    for segment in framing::decode_all::<Segment>(&output, screen::FRAMING).unwrap().frames {
+        screen.draw(segment);
+    }
+    screen.count(Tile::Block) as i32
+}
 
-    output.iter().chunks(3).into_iter().map(
-        |tile_with_pos| {
-            let tile_with_pos: Vec<&i64> = tile_with_pos.collect();
-            let tile = Tile::try_from(*tile_with_pos[2] as u8).unwrap();
-            if tile == Tile::Block { 1 } else { 0 }
+// Move the paddle under the ball.
+fn autopilot(screen: &Screen, _frame: &Frame) -> intcode::Input {
+    let x_of = |position: Option<(usize, usize)>| position.map_or(0, |(x, _)| x);
+    intcode::Input::Value(
+        match x_of(screen.paddle()).cmp(&x_of(screen.ball())) {
+            Ordering::Greater => -1,
+            Ordering::Less => 1,
+            Ordering::Equal => 0
         }
-    ).sum()
+    )
 }
 
-struct State {
-    score: i64,
-    joystick: i64, // -1: move to left, 0: do not move, 1: move to right.
-    paddle_position_x: i64,
-    ball_position_x: i64,
-    buffer: Vec<i64>, // Used to buffer the input.
+// Play for free.
+pub fn insert_quarters() -> intcode::Patch {
+    intcode::Patch::new("insert quarters", &[(0, 2)])
 }
 
-impl intcode::IO for State {
-    fn read(&mut self) -> i64 {
-        self.joystick
-    }
-
-    fn write(&mut self, value: i64) {
-        self.buffer.push(value);
-        if self.buffer.len() == 3 {
-            if self.buffer[0] == -1 && self.buffer[1] == 0 {
-                self.score = self.buffer[2];
-            } else {
-                let tile = Tile::try_from(self.buffer[2] as u8).unwrap();
-                if tile == Tile::Ball {
-                    self.ball_position_x = self.buffer[0];
-                } else if tile == Tile::Paddle {
-                    self.paddle_position_x = self.buffer[0];
-                }
-                self.joystick =
-                    match self.paddle_position_x.cmp(&self.ball_position_x) {
-                        Ordering::Greater => -1,
-                        Ordering::Less => 1,
-                        Ordering::Equal => 0
-                    };
-            }
-            self.buffer.clear();
-        }
-    }
+pub fn final_score(code: &[i64]) -> i64 {
+    let mut arcade = Framed::new(Arcade::new(autopilot), screen::FRAMING);
+    intcode::execute_op_code_with_custom_io(code, &mut arcade).unwrap();
+    arcade.into_result().unwrap().screen.score().unwrap_or(0)
 }
 
-pub fn final_score(code: &[i64]) -> i64 {
-    let mut state = State { score: 0, joystick: 0, paddle_position_x: 0, ball_position_x: 0, buffer: Vec::new() };
-    intcode::execute_op_code_with_custom_io(&code, &mut state);
-    state.score
+// Same as 'final_score' but the game played by the autopilot is also recorded.
+pub fn record_game(code: &[i64]) -> (i64, intcode::record::Session) {
+    let mut arcade = Framed::new(Arcade::new(autopilot), screen::FRAMING);
+    let mut session = intcode::record::Session::new();
+    intcode::record::record(&mut intcode::Machine::new(code), &mut arcade, &mut session).unwrap();
+    (arcade.into_result().unwrap().screen.score().unwrap_or(0), session)
 }
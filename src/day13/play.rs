@@ -0,0 +1,254 @@
+// A playable arcade: the joystick is moved with a keyboard and the autopilot can take over at any moment.
+// The keyboard can be a script so that games can be played without a terminal.
+
+use std::{fmt, io::{self, Write}, mem, sync::mpsc, thread, time::Duration};
+
+use crate::intcode::{self, framing::{FrameError, Framed}, screen::{self, Arcade, Controller, Frame, Screen, SegmentError, Tile}};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Key {
+    Left,
+    Neutral,
+    Right,
+    Autopilot, // Switch the autopilot on or off.
+    Quit,
+}
+
+impl Key {
+    // The same keys are used at the keyboard and in scripts: 'a' left, 's' neutral, 'd' right, 'p' autopilot and 'q' quit.
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            'a' => Some(Key::Left),
+            's' => Some(Key::Neutral),
+            'd' => Some(Key::Right),
+            'p' => Some(Key::Autopilot),
+            'q' => Some(Key::Quit),
+            _ => None
+        }
+    }
+
+    fn joystick(self) -> Option<i64> {
+        match self {
+            Key::Left => Some(-1),
+            Key::Neutral => Some(0),
+            Key::Right => Some(1),
+            Key::Autopilot | Key::Quit => None
+        }
+    }
+}
+
+pub trait Keyboard {
+    // The keys pressed since the previous call, called once per frame.
+    fn keys(&mut self) -> Vec<Key>;
+}
+
+// Keys sent by another thread, typically the one reading the terminal.
+impl Keyboard for mpsc::Receiver<Key> {
+    fn keys(&mut self) -> Vec<Key> { self.try_iter().collect() }
+}
+
+// One key per frame, no key is pressed once the script is over.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Script {
+    keys: Vec<Option<Key>>,
+    next: usize,
+}
+
+impl Script {
+    // One character per frame: a key (see 'Key::from_char') or '.' for no key.
+    // Whitespaces are ignored, return the first unknown character as error.
+    pub fn parse(script: &str) -> Result<Self, char> {
+        let keys = script.chars().filter(|c| !c.is_whitespace()).map(|c|
+            match c {
+                '.' => Ok(None),
+                c => Key::from_char(c).map(Some).ok_or(c)
+            }
+        ).collect::<Result<Vec<Option<Key>>, char>>()?;
+        Ok(Script { keys, next: 0 })
+    }
+}
+
+impl Keyboard for Script {
+    fn keys(&mut self) -> Vec<Key> {
+        let key = self.keys.get(self.next).copied().flatten();
+        self.next += 1;
+        key.into_iter().collect()
+    }
+}
+
+pub struct Player<K: Keyboard, W: Write> {
+    pub keyboard: K,
+    pub autopilot: bool,
+    pub joystick: i64, // Kept until another joystick key is pressed.
+    pub quit: bool,
+    pub nb_frames: usize,
+    output: Option<W>, // The screen is drawn with ANSI escape sequences, nothing is drawn without output.
+    delay: Duration, // After each frame drawn.
+    error: Option<io::Error>,
+}
+
+impl<K: Keyboard> Player<K, io::Sink> {
+    pub fn headless(keyboard: K) -> Self {
+        Player { keyboard, autopilot: false, joystick: 0, quit: false, nb_frames: 0, output: None, delay: Duration::from_secs(0), error: None }
+    }
+}
+
+impl<K: Keyboard, W: Write> Player<K, W> {
+    pub fn new(keyboard: K, output: W, delay: Duration) -> Self {
+        Player { keyboard, autopilot: false, joystick: 0, quit: false, nb_frames: 0, output: Some(output), delay, error: None }
+    }
+
+    fn mode(&self) -> &'static str {
+        if self.autopilot { "autopilot" } else { "manual" }
+    }
+
+    fn press(&mut self, key: Key) {
+        match key {
+            Key::Autopilot => self.autopilot = !self.autopilot,
+            Key::Quit => self.quit = true,
+            key => {
+                // Moving the joystick takes the control back.
+                self.joystick = key.joystick().unwrap();
+                self.autopilot = false;
+            }
+        }
+    }
+
+    // The whole screen for the first frame then only the changes, followed by a status line.
+    fn draw(&mut self, screen: &Screen, frame: &Frame, status: &str) {
+        let output = match (&mut self.output, &self.error) {
+            (Some(output), None) => output,
+            _ => return
+        };
+        let changes = if frame.number == 0 { format!("\x1b[H\x1b[2J{}", screen.render()) } else { screen.render_changes(frame) };
+        let result = write!(output, "{}\x1b[{};1H{}\x1b[K", changes, screen.height() + 2, status).and_then(|_| output.flush());
+        match result {
+            Ok(()) => thread::sleep(self.delay),
+            Err(error) => self.error = Some(error)
+        }
+    }
+}
+
+impl<K: Keyboard, W: Write> Controller for Player<K, W> {
+    fn input(&mut self, screen: &Screen, frame: &Frame) -> intcode::Input {
+        self.nb_frames += 1;
+        for key in self.keyboard.keys() {
+            self.press(key);
+        }
+        let status = format!("{} mode, {} blocks left", self.mode(), screen.count(Tile::Block));
+        self.draw(screen, frame, &status);
+
+        if self.autopilot {
+            let input = super::autopilot(screen, frame);
+            if let intcode::Input::Value(value) = input {
+                self.joystick = value;
+            }
+            input
+        } else {
+            intcode::Input::Value(self.joystick)
+        }
+    }
+
+    fn halt(&self) -> bool { self.quit || self.error.is_some() }
+
+    fn finished(&mut self, screen: &Screen, frame: &Frame) {
+        let status = if self.quit { "Quit" } else { "Game over" };
+        self.draw(screen, frame, status);
+        if let (Some(output), None) = (&mut self.output, &self.error) {
+            if let Err(error) = writeln!(output).and_then(|_| output.flush()) {
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub score: i64,
+    pub blocks_left: usize,
+    pub quit: bool, // The player has left before the end of the game.
+    pub nb_frames: usize,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let end = if self.quit { "quit" } else if self.blocks_left == 0 { "won" } else { "lost" };
+        write!(f, "Game {} after {} frames with a score of {}, {} blocks left", end, self.nb_frames, self.score, self.blocks_left)
+    }
+}
+
+#[derive(Debug)]
+pub enum PlayError {
+    Intcode(intcode::Error),
+    Screen(FrameError<SegmentError>),
+    Output(io::Error),
+}
+
+// Play the game of 'code' for free.
+pub fn play<K: Keyboard, W: Write>(code: &[i64], player: Player<K, W>) -> Result<Outcome, PlayError> {
+    let mut arcade = Framed::new(Arcade::new(player), screen::FRAMING);
+    intcode::execute_op_code_with_custom_io(&super::insert_quarters().apply(code), &mut arcade).map_err(PlayError::Intcode)?;
+    let mut arcade = arcade.into_result().map_err(PlayError::Screen)?;
+    if let Some(error) = mem::take(&mut arcade.controller.error) {
+        return Err(PlayError::Output(error))
+    }
+    Ok(Outcome {
+        score: arcade.screen.score().unwrap_or(0),
+        blocks_left: arcade.screen.count(Tile::Block),
+        quit: arcade.controller.quit,
+        nb_frames: arcade.controller.nb_frames,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common;
+
+    // Draw the ball at (2, 0) and the paddle at (1, 1), then four times: read the joystick, move the paddle
+    // horizontally by its value and show its x position as score. The first instruction only stores 0 * 0
+    // with or without the quarters.
+    const GAME: [i64; 56] = [
+        1,200,200,200, 104,2,104,0,104,4, 1101,1,0,101, 104,1,104,1,104,3,
+        3,100, 4,101,104,1,104,0, 1,101,100,101, 4,101,104,1,104,3, 104,-1,104,0,4,101,
+        1001,102,1,102, 1007,102,4,103, 1005,103,20, 99];
+
+    #[test]
+    fn scripted_keys() {
+        assert_eq!(Script::parse("a. s d\npq").map(|script| script.keys), Ok(vec![Some(Key::Left), None, Some(Key::Neutral), Some(Key::Right), Some(Key::Autopilot), Some(Key::Quit)]));
+        assert_eq!(Script::parse("ax"), Err('x'));
+
+        let mut script = Script::parse("a.").unwrap();
+        assert_eq!((script.keys(), script.keys(), script.keys()), (vec![Key::Left], vec![], vec![]));
+    }
+
+    #[test]
+    fn manual_and_autopilot() {
+        // The joystick stays on the right while no key is pressed.
+        let outcome = play(&GAME, Player::headless(Script::parse("d.sa").unwrap())).unwrap();
+        assert_eq!(outcome, Outcome { score: 2, blocks_left: 0, quit: false, nb_frames: 4 });
+
+        // The autopilot moves the paddle under the ball until the joystick is moved.
+        let outcome = play(&GAME, Player::headless(Script::parse("p.a.").unwrap())).unwrap();
+        assert_eq!(outcome.score, 0);
+        assert_eq!(outcome.to_string(), "Game won after 4 frames with a score of 0, 0 blocks left");
+    }
+
+    #[test]
+    fn quit_and_draw() {
+        let mut output = Vec::new();
+        let outcome = play(&GAME, Player::new(Script::parse("daq").unwrap(), &mut output, Duration::from_secs(0))).unwrap();
+        assert_eq!(outcome, Outcome { score: 1, blocks_left: 0, quit: true, nb_frames: 3 });
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("\x1b[H\x1b[2J  ●\n ▬ \x1b[4;1Hmanual mode, 0 blocks left\x1b[K"));
+        assert!(output.ends_with("\x1b[4;1HQuit\x1b[K\n"));
+    }
+
+    #[test]
+    fn autopilot_wins() {
+        let code = common::read_list_of_numbers::<&str, i64>("data/day13.input", ",");
+        let outcome = play(&code, Player::headless(Script::parse("p").unwrap())).unwrap();
+        assert_eq!((outcome.score, outcome.blocks_left, outcome.quit), (15957, 0, false));
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::{HashMap, HashSet}, iter::FromIterator};
+use std::{collections::{HashMap, HashSet, VecDeque}, iter::FromIterator};
 
 use super::intcode;
 
@@ -22,7 +22,7 @@ pub struct DroidTrackingSystem {
 impl DroidTrackingSystem {
     fn new() -> Self {
         DroidTrackingSystem {
-            board: HashMap::from_iter(vec![((0, 0), LocationState::Visited)].into_iter()),
+            board: HashMap::from_iter(vec![((0, 0), LocationState::Visited)]),
             current_path: vec![(0, 0)],
             oxygen_location: (0, 0),
             steps_to_oxygen: 0,
@@ -90,8 +90,8 @@ impl DroidTrackingSystem {
 
 impl intcode::IO for DroidTrackingSystem {
     // May block.
-    fn read(&mut self) -> i64 {
-        self.next_movement()
+    fn read(&mut self) -> intcode::Input {
+        intcode::Input::Value(self.next_movement())
     }
 
     // Send to the output channel.
@@ -106,10 +106,55 @@ impl intcode::IO for DroidTrackingSystem {
 
 pub fn nb_of_movement_to_reach_oxygen(code: &[i64]) -> (i32, DroidTrackingSystem) {
     let mut dts = DroidTrackingSystem::new();
-    intcode::execute_op_code_with_custom_io(code, &mut dts);
+    intcode::execute_op_code_with_custom_io(code, &mut dts).unwrap();
     (dts.steps_to_oxygen, dts)
 }
 
+// Record the whole exploration of the area.
+pub fn record_exploration(code: &[i64]) -> intcode::record::Session {
+    let mut dts = DroidTrackingSystem::new();
+    let mut session = intcode::record::Session::new();
+    intcode::record::record(&mut intcode::Machine::new(code), &mut dts, &mut session).unwrap();
+    session
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SearchError {
+    Intcode(intcode::Error),
+    UnexpectedReply { location: (i32, i32), reply: Option<i64> }, // The droid must reply with a single status code.
+    OxygenSystemNotFound,
+}
+
+impl From<intcode::Error> for SearchError {
+    fn from(error: intcode::Error) -> Self { SearchError::Intcode(error) }
+}
+
+// Breadth-first search of the oxygen system: at each location the droid program is forked to try each direction.
+pub fn nb_of_movement_to_reach_oxygen_by_forking(code: &[i64]) -> Result<i32, SearchError> {
+    let mut visited: HashSet<(i32, i32)> = HashSet::from_iter(vec![(0, 0)]);
+    let mut locations = VecDeque::from(vec![(intcode::Machine::new(code), (0, 0), 0)]);
+
+    while let Some((mut droid, (x, y), steps)) = locations.pop_front() {
+        for (mov, pos) in [(1, (x, y + 1)), (2, (x, y - 1)), (3, (x - 1, y)), (4, (x + 1, y))] {
+            if !visited.insert(pos) { continue }
+
+            let mut droid = droid.fork();
+            let mut buffer = intcode::Buffer::new();
+            buffer.push(mov);
+            droid.run(&mut buffer)?;
+
+            match buffer.output[..] {
+                [0] => (),
+                [1] => locations.push_back((droid, pos, steps + 1)),
+                [2] => return Ok(steps + 1),
+                _ => return Err(SearchError::UnexpectedReply { location: pos, reply: buffer.output.first().copied() })
+            }
+        }
+    }
+
+    Err(SearchError::OxygenSystemNotFound)
+}
+
 pub fn time_to_flood_the_area(dts: &DroidTrackingSystem) -> i32 {
     let mut dts = dts.clone(); // To be mutable.
     dts.current_path = vec![dts.oxygen_location];
@@ -130,4 +175,27 @@ pub fn time_to_flood_the_area(dts: &DroidTrackingSystem) -> i32 {
     }
 
     max_length - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common;
+
+    #[test]
+    fn part1_by_forking() {
+        let code = common::read_list_of_numbers("data/day15.input", ",");
+        let (n, _) = nb_of_movement_to_reach_oxygen(&code);
+        assert_eq!(nb_of_movement_to_reach_oxygen_by_forking(&code), Ok(n));
+    }
+
+    #[test]
+    fn search_errors() {
+        // Halts without replying.
+        assert_eq!(nb_of_movement_to_reach_oxygen_by_forking(&[3, 3, 99]), Err(SearchError::UnexpectedReply { location: (0, 1), reply: None }));
+        // Always hits a wall.
+        assert_eq!(nb_of_movement_to_reach_oxygen_by_forking(&[3, 7, 104, 0, 1105, 1, 0, 0]), Err(SearchError::OxygenSystemNotFound));
+        // Unknown op code.
+        assert_eq!(nb_of_movement_to_reach_oxygen_by_forking(&[3, 3, 42]), Err(SearchError::Intcode(intcode::Error::UnknownOpCode { cursor: 2, op_code: 42 })));
+    }
 }
\ No newline at end of file
@@ -166,7 +166,7 @@ impl CommandSequences {
                         let mut position2 = position + l;
                         while position2 <= movements.len() - l {
                             let range2 = position2 .. position2 + l;
-                            if !self.commands.iter().any(|(_, r)| is_overlapping(&r, &range2)) && movements.get(range.clone()) == movements.get(range2.clone()) {
+                            if !self.commands.iter().any(|(_, r)| is_overlapping(r, &range2)) && movements.get(range.clone()) == movements.get(range2.clone()) {
                                 self.commands.push((seq_num, range2));
                                 position2 += l;
                             } else {
@@ -207,8 +207,8 @@ struct Part2 {
 impl Part2 { fn new() -> Self { Part2 { output: Vec::<i64>::new(), rts: None, commands_sequences: CommandSequences::new(), input: Vec::new(), input_position: 0, dust_collected: 0 } } }
 
 impl intcode::IO for Part1 {
-    // Read instructions
-    fn read(&mut self) -> i64 { 0 }
+    // The camera doesn't need any instruction.
+    fn read(&mut self) -> intcode::Input { intcode::Input::Closed }
 
     // Send to the output channel.
     fn write(&mut self, value: i64) {
@@ -218,7 +218,7 @@ impl intcode::IO for Part1 {
 
 impl intcode::IO for Part2 {
     // Read instructions.
-    fn read(&mut self) -> i64 {
+    fn read(&mut self) -> intcode::Input {
         if self.rts.is_none() {
             self.rts = Some(RobotTrackingSystem::from(&self.output));
             self.commands_sequences.find_sequences(&self.rts.as_ref().unwrap().dir_commands);
@@ -250,8 +250,13 @@ impl intcode::IO for Part2 {
             self.input.push(10);
         }
 
-        self.input_position += 1;
-        self.input[self.input_position - 1]
+        match self.input.get(self.input_position) {
+            Some(value) => {
+                self.input_position += 1;
+                intcode::Input::Value(*value)
+            },
+            None => intcode::Input::Closed
+        }
     }
 
     // Send to the output channel.
@@ -266,13 +271,18 @@ impl intcode::IO for Part2 {
 
 pub fn scaffold_intersections(code: &[i64]) -> i32 {
     let mut part1 = Part1::new();
-    intcode::execute_op_code_with_custom_io(code, &mut part1);
+    intcode::execute_op_code_with_custom_io(code, &mut part1).unwrap();
     let rts = RobotTrackingSystem::from(&part1.output);
     rts.crossings.iter().fold(0, |sum, crossing| sum + crossing.0 * crossing.1)
 }
 
+// Wake up the vacuum robot so it can be controlled.
+pub fn wake_up_robot() -> intcode::Patch {
+    intcode::Patch::new("wake up robot", &[(0, 2)])
+}
+
 pub fn collected_dust(code: &[i64]) -> i64 {
     let mut part2 = Part2::new();
-    intcode::execute_op_code_with_custom_io(code, &mut part2);
+    intcode::execute_op_code_with_custom_io(code, &mut part2).unwrap();
     part2.dust_collected
 }
\ No newline at end of file
@@ -1,41 +1,130 @@
-#[derive(Copy, Clone, Debug)]
+// The core of the interpreter only depends on 'core' and 'alloc' so it can be embedded in a 'no_std' environment.
+// The conveniences relying on 'std' (buffer, batches, recording, etc.) are enabled by the 'std' feature.
+
+use alloc::{format, string::String, vec::Vec};
+use core::convert::TryFrom;
+
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+mod buffer;
+#[cfg(feature = "std")]
+pub mod codegen;
+#[cfg(feature = "std")]
+pub mod dap;
+#[cfg(feature = "std")]
+pub mod disassembler;
+pub mod framing;
+pub mod memory;
+#[cfg(feature = "std")]
+pub mod optimizer;
+#[cfg(feature = "std")]
+pub mod record;
+#[cfg(feature = "std")]
+pub mod screen;
+#[cfg(feature = "std")]
+pub mod strings;
+#[cfg(feature = "std")]
+pub mod taint;
+
+#[cfg(feature = "std")]
+pub use buffer::{Buffer, execute_op_code};
+use memory::Memory;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 enum Mode {
     Position,
     Immediate,
     Relative
 }
 
+// What an 'IO' can supply when the machine executes an input instruction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Input {
+    Value(i64),
+    Pending, // No value available yet: the machine is suspended and can be resumed later.
+    Closed, // No value will ever be available: the machine terminates with an error.
+}
+
+// A read-only view of the machine given to the IO when an input or output instruction is executed.
+#[derive(Copy, Clone, Debug)]
+pub struct Context<'a> {
+    pub cursor: usize, // Address of the input or output instruction.
+    pub relative_base: i64,
+    pub instruction_count: u64, // Number of instructions executed before this one.
+    pub memory: &'a Memory,
+}
+
 pub trait IO {
-    fn read(&mut self) -> i64;
+    fn read(&mut self) -> Input;
     fn write(&mut self, value: i64);
     fn halt(&self) -> bool { false }
     fn finished(&mut self) { }
+
+    // Called by the machine instead of 'read' and 'write', to be implemented by an IO needing the context.
+    fn read_with_context(&mut self, _context: &Context) -> Input { self.read() }
+    fn write_with_context(&mut self, value: i64, _context: &Context) { self.write(value) }
 }
 
-struct Buffer {
-    output: Vec<i64>,
-    input: Vec<i64>
+// Op codes accepted by a machine.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InstructionSet {
+    Day02, // Only sum (1), multiply (2) and halt (99) in position mode.
+    Complete,
 }
 
-impl Buffer {
-    fn from(input: &[i64]) -> Self {
-        Buffer {
-            output: Vec::new(),
-            input: Vec::from(input)
-        }
-    }
+fn supported_by_day02(op_code: i64) -> bool {
+    op_code == 1 || op_code == 2 || op_code == 99
+}
+
+fn supported_by_complete(_op_code: i64) -> bool {
+    true
+}
+
+// A named set of values written to a program before running it, for example
+// to set the noun and verb (day 2) or to insert quarters (day 13).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Patch {
+    pub name: String,
+    pub writes: Vec<(usize, i64)>, // Addresses and their new value.
 }
 
-impl IO for Buffer {
-    fn read(&mut self) -> i64 {
-        self.input.remove(0)
+impl Patch {
+    pub fn new(name: &str, writes: &[(usize, i64)]) -> Self {
+        Patch { name: String::from(name), writes: Vec::from(writes) }
     }
 
-    fn write(&mut self, value: i64) {
-        self.output.push(value)
+    pub fn noun_and_verb(noun: i64, verb: i64) -> Self {
+        Patch::new(&format!("noun {} and verb {}", noun, verb), &[(1, noun), (2, verb)])
+    }
+
+    // Return a patched copy of the given program.
+    pub fn apply(&self, code: &[i64]) -> Vec<i64> {
+        let mut code = Vec::from(code);
+        for (address, value) in self.writes.iter() {
+            if *address >= code.len() {
+                code.resize(address + 1, 0);
+            }
+            code[*address] = *value;
+        }
+        code
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    Halted,
+    WaitingForInput,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    UnknownOpCode { cursor: usize, op_code: i64 },
+    WriteInImmediateMode { cursor: usize },
+    InputClosed { cursor: usize },
+    InvalidAddress { cursor: usize }, // A negative address or an overflow of the address or the relative base.
+}
+
 // 'true' -> immediate mode, 'false' -> position mode.
 fn read_op_and_modes(mut code: i64) -> (i64, [Mode; 3]) {
     let mut modes: [Mode; 3] = [ Mode::Position, Mode::Position, Mode::Position ];
@@ -54,115 +143,253 @@ fn read_op_and_modes(mut code: i64) -> (i64, [Mode; 3]) {
     (code, modes)
 }
 
-pub fn execute_op_code(code: &[i64], input: &[i64]) -> Vec<i64> {
-    let mut buffer = Buffer::from(input);
-    execute_op_code_with_custom_io(code, &mut buffer);
-    buffer.output
+// Run the program until it halts. If the IO has no input available yet the state of the program is lost,
+// use 'Machine' to be able to resume it.
+pub fn execute_op_code_with_custom_io(code: &[i64], io: &mut dyn IO) -> Result<Status, Error> {
+    Machine::new(code).run(io)
+}
+
+// A program with its state, can be suspended when its input isn't ready and resumed later.
+#[derive(Clone, Debug)]
+pub struct Machine {
+    code: Memory,
+    cursor: usize,
+    relative_base: i64,
+    instruction_count: u64, // Number of executed instructions.
+    halted: bool,
+    instruction_set: InstructionSet,
 }
 
-pub fn execute_op_code_with_custom_io(code: &[i64], io: &mut dyn IO) {
-    let mut code = Vec::from(code);
-    let mut cursor = 0;
-    let mut relative_base = 0;
+#[inline(always)]
+fn to_address(value: Option<i64>, cursor: usize) -> Result<usize, Error> {
+    value.and_then(|value| usize::try_from(value).ok()).ok_or(Error::InvalidAddress { cursor })
+}
 
-    fn read(position: usize, code: &[i64], mode: Mode, relative_base: i64) -> i64 {
-        if position >= code.len() { return 0 }
+// Address of the cell of the parameter 'n' (from 0) of the instruction at 'cursor'.
+#[inline(always)]
+fn address(cursor: usize, n: usize, code: &Memory, mode: Mode, relative_base: i64) -> Result<usize, Error> {
+    let position = cursor + 1 + n;
+    match mode {
+        Mode::Position => to_address(Some(code.get(position)), cursor),
+        Mode::Immediate => Ok(position),
+        Mode::Relative => to_address(code.get(position).checked_add(relative_base), cursor)
+    }
+}
 
-        match mode {
-            Mode::Position => if code[position] as usize >= code.len() { 0 } else { code[code[position] as usize] },
-            Mode::Immediate => code[position],
-            Mode::Relative => if (code[position] + relative_base) as usize >= code.len() { 0 } else { code[(code[position] + relative_base) as usize] }
+#[inline(always)]
+fn read(cursor: usize, n: usize, code: &Memory, mode: Mode, relative_base: i64) -> Result<i64, Error> {
+    Ok(code.get(address(cursor, n, code, mode, relative_base)?))
+}
+
+#[inline(always)]
+fn write(cursor: usize, n: usize, value: i64, code: &mut Memory, mode: Mode, relative_base: i64) -> Result<(), Error> {
+    if mode == Mode::Immediate {
+        return Err(Error::WriteInImmediateMode { cursor })
+    }
+    let address = address(cursor, n, code, mode, relative_base)?;
+    code.set(address, value);
+    Ok(())
+}
+
+#[inline(always)]
+fn jump_if(cond: bool, cursor: usize, code: &Memory, modes: [Mode; 3], relative_base: i64) -> Result<usize, Error> {
+    let value = read(cursor, 0, code, modes[0], relative_base)?;
+    if cond == (value != 0) {
+        to_address(Some(read(cursor, 1, code, modes[1], relative_base)?), cursor)
+    } else {
+        Ok(cursor + 3)
+    }
+}
+
+impl Machine {
+    pub fn new(code: &[i64]) -> Self {
+        Machine::with_instruction_set(code, InstructionSet::Complete)
+    }
+
+    pub fn with_instruction_set(code: &[i64], instruction_set: InstructionSet) -> Self {
+        Machine {
+            code: Memory::from(code),
+            cursor: 0,
+            relative_base: 0,
+            instruction_count: 0,
+            halted: false,
+            instruction_set
         }
     }
 
-    fn write(position: usize, value: i64, code: &mut Vec<i64>, mode: Mode, relative_base: i64) {
-        let ref_position =
-            match mode {
-                Mode::Position => code[position] as usize,
-                Mode::Immediate => panic!("Can't write un immediate mode"),
-                Mode::Relative => (code[position] + relative_base) as usize
-            };
+    pub fn memory(&self) -> &Memory { &self.code }
+
+    pub fn instruction_count(&self) -> u64 { self.instruction_count }
+
+    pub fn cursor(&self) -> usize { self.cursor }
+
+    pub fn relative_base(&self) -> i64 { self.relative_base }
 
-        if ref_position >= code.len() {
-            code.resize(ref_position + 1, 0);
+    pub fn is_halted(&self) -> bool { self.halted }
+
+    // A copy of the machine in its current state, the memory of both machines is paged and shared until written.
+    pub fn fork(&mut self) -> Self {
+        self.code.make_paged();
+        self.clone()
+    }
+
+    // Run until the program halts or waits for an input, in the latter case it can be resumed by calling 'run' again.
+    // The instruction set is chosen once here, the check of each op code is then inlined (and removed for 'Complete').
+    pub fn run(&mut self, io: &mut dyn IO) -> Result<Status, Error> {
+        match self.instruction_set {
+            InstructionSet::Day02 => self.run_with(io, supported_by_day02),
+            InstructionSet::Complete => self.run_with(io, supported_by_complete)
         }
+    }
 
-        code[ref_position] = value;
+    fn run_with<F: Fn(i64) -> bool + Copy>(&mut self, io: &mut dyn IO, supports: F) -> Result<Status, Error> {
+        loop {
+            if let Some(status) = self.execute(io, supports)? {
+                return Ok(status)
+            }
+        }
     }
 
-    fn jump_if(cond: bool, cursor: usize, code: &[i64], modes: [Mode; 3], relative_base: i64) -> usize {
-        let value = read(cursor + 1, &code, modes[0], relative_base);
-        if cond == (value != 0) {
-            read(cursor + 2, &code, modes[1], relative_base) as usize
-        } else {
-            cursor + 3
+    // Execute one instruction, return a status if the program has halted or waits for an input.
+    pub fn step(&mut self, io: &mut dyn IO) -> Result<Option<Status>, Error> {
+        match self.instruction_set {
+            InstructionSet::Day02 => self.execute(io, supported_by_day02),
+            InstructionSet::Complete => self.execute(io, supported_by_complete)
         }
     }
 
-    loop {
-        if io.halt() { break; }
+    #[inline(always)]
+    fn execute<F: Fn(i64) -> bool>(&mut self, io: &mut dyn IO, supports: F) -> Result<Option<Status>, Error> {
+        if self.halted { return Ok(Some(Status::Halted)) }
 
-        let (op, modes) = read_op_and_modes(code[cursor]);
+        if io.halt() {
+            return Ok(Some(self.halt(io)))
+        }
+
+        let code = &mut self.code;
+        let cursor = &mut self.cursor;
+        let relative_base = &mut self.relative_base;
+        let instruction_count = self.instruction_count;
+
+        if !supports(code.get(*cursor)) {
+            return Err(Error::UnknownOpCode { cursor: *cursor, op_code: code.get(*cursor) })
+        }
+
+        let (op, modes) = read_op_and_modes(code.get(*cursor));
 
         match op {
             // Sum.
             1 => {
-                write(cursor + 3, read(cursor + 1, &code, modes[0], relative_base) + read(cursor + 2, &code, modes[1], relative_base), &mut code, modes[2], relative_base);
-                cursor += 4;
+                write(*cursor, 2, read(*cursor, 0, code, modes[0], *relative_base)? + read(*cursor, 1, code, modes[1], *relative_base)?, code, modes[2], *relative_base)?;
+                *cursor += 4;
             },
 
             // Multiply.
             2 => {
-                write(cursor + 3, read(cursor + 1, &code, modes[0], relative_base) * read(cursor + 2, &code, modes[1], relative_base), &mut code, modes[2], relative_base);
-                cursor += 4;
+                write(*cursor, 2, read(*cursor, 0, code, modes[0], *relative_base)? * read(*cursor, 1, code, modes[1], *relative_base)?, code, modes[2], *relative_base)?;
+                *cursor += 4;
             },
 
             // Input.
             3 => {
-                write(cursor + 1, io.read(), &mut code, modes[0], relative_base);
-                cursor += 2;
+                match io.read_with_context(&Context { cursor: *cursor, relative_base: *relative_base, instruction_count, memory: code }) {
+                    Input::Value(value) => write(*cursor, 0, value, code, modes[0], *relative_base)?,
+                    Input::Pending => return Ok(Some(Status::WaitingForInput)),
+                    Input::Closed => return Err(Error::InputClosed { cursor: *cursor })
+                }
+                *cursor += 2;
             }
 
             // Output.
             4 => {
-                io.write(read(cursor + 1, &code, modes[0], relative_base));
-                cursor += 2;
+                let value = read(*cursor, 0, code, modes[0], *relative_base)?;
+                io.write_with_context(value, &Context { cursor: *cursor, relative_base: *relative_base, instruction_count, memory: code });
+                *cursor += 2;
             }
 
             // Jump-if-true.
-            5 => cursor = jump_if(true, cursor, &code, modes, relative_base),
+            5 => *cursor = jump_if(true, *cursor, code, modes, *relative_base)?,
 
             // Jump-if-false.
-            6 => cursor = jump_if(false, cursor, &code, modes, relative_base),
+            6 => *cursor = jump_if(false, *cursor, code, modes, *relative_base)?,
 
             // Less than.
             7 => {
-                write(cursor + 3, if read(cursor + 1, &code, modes[0], relative_base) < read(cursor + 2, &code, modes[1], relative_base) { 1 } else { 0 }, &mut code, modes[2], relative_base);
-                cursor += 4;
+                write(*cursor, 2, if read(*cursor, 0, code, modes[0], *relative_base)? < read(*cursor, 1, code, modes[1], *relative_base)? { 1 } else { 0 }, code, modes[2], *relative_base)?;
+                *cursor += 4;
             },
 
             // Equals.
             8 => {
-                write(cursor + 3, if read(cursor + 1, &code, modes[0], relative_base) == read(cursor + 2, &code, modes[1], relative_base) { 1 } else { 0 }, &mut code, modes[2], relative_base);
-                cursor += 4;
+                write(*cursor, 2, if read(*cursor, 0, code, modes[0], *relative_base)? == read(*cursor, 1, code, modes[1], *relative_base)? { 1 } else { 0 }, code, modes[2], *relative_base)?;
+                *cursor += 4;
             },
 
             // Change relative base.
             9 => {
-                relative_base += read(cursor + 1, &code, modes[0], relative_base);
-                cursor += 2;
+                *relative_base = relative_base.checked_add(read(*cursor, 0, code, modes[0], *relative_base)?).ok_or(Error::InvalidAddress { cursor: *cursor })?;
+                *cursor += 2;
             }
 
-            99 => break,
+            99 => {
+                self.instruction_count += 1;
+                return Ok(Some(self.halt(io)))
+            }
 
-            _ => panic!("Unknown code: {}", code[cursor])
+            _ => return Err(Error::UnknownOpCode { cursor: *cursor, op_code: code.get(*cursor) })
         }
+
+        self.instruction_count += 1;
+        Ok(None)
     }
 
-    io.finished();
+    fn halt(&mut self, io: &mut dyn IO) -> Status {
+        self.halted = true;
+        io.finished();
+        Status::Halted
+    }
 }
 
 #[cfg(test)]
+mod tests_core {
+    use super::*;
+
+    // An IO without any allocation, as it could be implemented on an embedded target.
+    struct Register {
+        input: Option<i64>,
+        output: i64,
+    }
+
+    impl IO for Register {
+        fn read(&mut self) -> Input {
+            match self.input.take() {
+                Some(value) => Input::Value(value),
+                None => Input::Pending
+            }
+        }
+
+        fn write(&mut self, value: i64) {
+            self.output = value
+        }
+    }
+
+    #[test]
+    fn run_without_std() {
+        // Output three times the input, twice.
+        let c = [3,15,1002,15,3,15,4,15,3,15,1002,15,3,15,4,15,99];
+        let mut machine = Machine::new(&c);
+        let mut register = Register { input: Some(4), output: 0 };
+        assert_eq!(machine.run(&mut register), Ok(Status::WaitingForInput));
+        assert_eq!(register.output, 12);
+
+        register.input = Some(5);
+        assert_eq!(machine.run(&mut register), Ok(Status::Halted));
+        assert_eq!(register.output, 15);
+        assert_eq!(machine.instruction_count(), 7);
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests_day05 {
     use super::*;
 
@@ -181,7 +408,7 @@ mod tests_day05 {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests_day09 {
     use super::*;
 
@@ -205,4 +432,125 @@ mod tests_day09 {
         let r = execute_op_code(&c, &Vec::new());
         assert_eq!(r[0], 1_125_899_906_842_624);
     }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests_machine {
+    use super::*;
+
+    // Answer the value stored at the address given by the previous output and log the IO with their address.
+    struct Peeker {
+        address: usize,
+        log: Vec<String>,
+    }
+
+    impl IO for Peeker {
+        fn read(&mut self) -> Input { Input::Closed }
+
+        fn write(&mut self, _value: i64) { }
+
+        fn read_with_context(&mut self, context: &Context) -> Input {
+            let value = context.memory.get(self.address);
+            self.log.push(format!("{}: read {} (rb {}, instruction {})", context.cursor, value, context.relative_base, context.instruction_count));
+            Input::Value(value)
+        }
+
+        fn write_with_context(&mut self, value: i64, context: &Context) {
+            self.address = value as usize;
+            self.log.push(format!("{}: write {}", context.cursor, value));
+        }
+    }
+
+    #[test]
+    fn context_in_callbacks() {
+        // Output 11 then read a value, write it back and halt.
+        let c = [109,3,104,11,3,12,4,12,99,0,0,42,0];
+        let mut peeker = Peeker { address: 0, log: Vec::new() };
+        assert_eq!(Machine::new(&c).run(&mut peeker), Ok(Status::Halted));
+        assert_eq!(peeker.log, vec!["2: write 11", "4: read 42 (rb 3, instruction 2)", "6: write 42"]);
+    }
+
+    #[test]
+    fn suspend_and_resume() {
+        // Output twice the input until the input is closed.
+        let c = [3,11,1002,11,2,12,4,12,1105,1,0,0,0];
+        let mut machine = Machine::new(&c);
+        let mut buffer = Buffer::new();
+
+        assert_eq!(machine.run(&mut buffer), Ok(Status::WaitingForInput));
+        assert!(buffer.output.is_empty());
+
+        buffer.push(3);
+        buffer.push(5);
+        assert_eq!(machine.run(&mut buffer), Ok(Status::WaitingForInput));
+        assert_eq!(buffer.output, vec![6, 10]);
+
+        buffer.push(7);
+        buffer.close();
+        assert_eq!(machine.run(&mut buffer), Err(Error::InputClosed { cursor: 0 }));
+        assert_eq!(buffer.output, vec![6, 10, 14]);
+    }
+
+    #[test]
+    fn fork() {
+        // Output the input plus the previous sum.
+        let c = [3,11,1,11,12,12,4,12,1105,1,0,0,0];
+        let mut machine = Machine::new(&c);
+        let mut buffer = Buffer::new();
+        buffer.push(3);
+        assert_eq!(machine.run(&mut buffer), Ok(Status::WaitingForInput));
+
+        let mut fork = machine.fork();
+        assert_eq!(fork.memory().nb_shared_pages(machine.memory()), 1);
+
+        // A fork can be moved to another thread.
+        fn assert_send<T: Send>(_: &T) {}
+        assert_send(&fork);
+
+        let mut fork_buffer = Buffer::new();
+        fork_buffer.push(10);
+        fork.run(&mut fork_buffer).unwrap();
+        buffer.push(20);
+        machine.run(&mut buffer).unwrap();
+
+        assert_eq!(buffer.output, vec![3, 23]);
+        assert_eq!(fork_buffer.output, vec![13]);
+        assert_eq!(fork.memory().nb_shared_pages(machine.memory()), 0);
+    }
+
+    #[test]
+    fn day02_instruction_set() {
+        let c = [1101,1,1,0,99];
+        let mut machine = Machine::with_instruction_set(&c, InstructionSet::Day02);
+        assert_eq!(machine.run(&mut Buffer::new()), Err(Error::UnknownOpCode { cursor: 0, op_code: 1101 }));
+
+        let mut machine = Machine::with_instruction_set(&[1,0,0,0,99], InstructionSet::Day02);
+        assert_eq!(machine.run(&mut Buffer::new()), Ok(Status::Halted));
+        assert_eq!(machine.memory().to_vec(), [2,0,0,0,99]);
+    }
+
+    #[test]
+    fn patch() {
+        let patch = Patch::noun_and_verb(12, 2);
+        assert_eq!(patch.name, "noun 12 and verb 2");
+        assert_eq!(patch.apply(&[1,0,0,3,99]), vec![1,12,2,3,99]);
+        assert_eq!(Patch::new("extend", &[(6, 1)]).apply(&[99]), vec![99,0,0,0,0,0,1]);
+    }
+
+    #[test]
+    fn unknown_op_code() {
+        let mut buffer = Buffer::new();
+        assert_eq!(execute_op_code_with_custom_io(&[1101,1,1,0,42], &mut buffer), Err(Error::UnknownOpCode { cursor: 4, op_code: 42 }));
+    }
+
+    #[test]
+    fn invalid_addresses() {
+        let run = |code: &[i64]| execute_op_code_with_custom_io(code, &mut Buffer::new());
+        assert_eq!(run(&[1101,1,1,0,11101,1,1,0,99]), Err(Error::WriteInImmediateMode { cursor: 4 }));
+        assert_eq!(run(&[1,-1,0,0,99]), Err(Error::InvalidAddress { cursor: 0 }));
+        assert_eq!(run(&[1101,1,1,-5,99]), Err(Error::InvalidAddress { cursor: 0 }));
+        assert_eq!(run(&[1105,1,-3]), Err(Error::InvalidAddress { cursor: 0 }));
+        assert_eq!(run(&[109,i64::MAX,204,1,99]), Err(Error::InvalidAddress { cursor: 2 }));
+        assert_eq!(run(&[109,i64::MAX,109,1,99]), Err(Error::InvalidAddress { cursor: 2 }));
+    }
 }
\ No newline at end of file
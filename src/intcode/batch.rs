@@ -0,0 +1,112 @@
+use std::{panic::{self, AssertUnwindSafe}, sync::{Arc, mpsc}};
+
+use threadpool::ThreadPool;
+
+use super::{Buffer, Error, Machine};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RunError {
+    Intcode(Error),
+    Panicked(String),
+}
+
+// Execute the program once per input vector on the pool threads. The results (outputs of each run) are in the same order as the inputs,
+// a run failing or panicking doesn't affect the others.
+pub fn execute_batch<I>(code: &[i64], inputs: I, pool: &ThreadPool) -> Vec<Result<Vec<i64>, RunError>>
+where
+    I: IntoIterator<Item = Vec<i64>>
+{
+    let code: Arc<[i64]> = Arc::from(code);
+    let (sender, receiver) = mpsc::channel::<(usize, Result<Vec<i64>, RunError>)>();
+
+    let mut nb_runs = 0;
+    for (i, input) in inputs.into_iter().enumerate() {
+        let code = code.clone();
+        let sender = sender.clone();
+        pool.execute(
+            move || {
+                let result =
+                    match panic::catch_unwind(AssertUnwindSafe(|| run(&code, &input))) {
+                        Ok(result) => result,
+                        Err(payload) => Err(RunError::Panicked(panic_message(payload)))
+                    };
+                sender.send((i, result)).unwrap_or_default();
+            }
+        );
+        nb_runs += 1;
+    }
+    drop(sender);
+
+    let mut results: Vec<Option<Result<Vec<i64>, RunError>>> = vec![None; nb_runs];
+    for (i, result) in receiver.iter() {
+        results[i] = Some(result);
+    }
+
+    results.into_iter().map(|result| result.unwrap_or_else(|| Err(RunError::Panicked(String::from("no result"))))).collect()
+}
+
+fn run(code: &[i64], input: &[i64]) -> Result<Vec<i64>, RunError> {
+    let mut buffer = Buffer::from(input);
+    Machine::new(code).run(&mut buffer).map_err(RunError::Intcode)?;
+    Ok(buffer.output)
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or_else(|| String::from("unknown panic"), |message| String::from(*message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_in_input_order() {
+        // Output 999 if the input is lower than 8, 1000 if it is equal to 8 and 1001 if it is greater than 8.
+        let c = [3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99];
+        let pool = ThreadPool::new(4);
+
+        let results = execute_batch(&c, (0 .. 100).map(|i| vec![i % 10]), &pool);
+        assert_eq!(results.len(), 100);
+        for (i, result) in results.iter().enumerate() {
+            let expected = match (i % 10).cmp(&8) { std::cmp::Ordering::Less => 999, std::cmp::Ordering::Equal => 1000, std::cmp::Ordering::Greater => 1001 };
+            assert_eq!(result, &Ok(vec![expected]));
+        }
+    }
+
+    #[test]
+    fn puzzle_input() {
+        // The amplifier of day 7 with every phase and some signals, compared to sequential runs.
+        let code = crate::common::read_list_of_numbers::<&str, i64>("data/day07.input", ",");
+        let inputs: Vec<Vec<i64>> = (0 .. 5).flat_map(|phase| (0 .. 20).map(move |signal| vec![phase, signal * 37])).collect();
+        let expected: Vec<Result<Vec<i64>, RunError>> = inputs.iter().map(|input| Ok(super::super::execute_op_code(&code, input))).collect();
+        assert_eq!(execute_batch(&code, inputs, &ThreadPool::new(3)), expected);
+    }
+
+    #[test]
+    fn errors_are_isolated() {
+        // Output the input.
+        let c = [3,0,4,0,99];
+        let pool = ThreadPool::new(2);
+
+        let results = execute_batch(&c, vec![vec![1], vec![], vec![3]], &pool);
+        assert_eq!(results, vec![Ok(vec![1]), Err(RunError::Intcode(Error::InputClosed { cursor: 0 })), Ok(vec![3])]);
+    }
+
+    #[test]
+    fn panics_are_isolated() {
+        // Write 5 at the address given as input then output it, the memory can't grow up to 2^62 cells.
+        let c = [3,5,1101,2,3,0,4,5,99];
+        let pool = ThreadPool::new(2);
+
+        let results = execute_batch(&c, vec![vec![10], vec![1 << 62], vec![20], vec![1 << 62], vec![5]], &pool);
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0], Ok(vec![10]));
+        assert!(matches!(&results[1], Err(RunError::Panicked(message)) if message.contains("capacity overflow")));
+        assert_eq!(results[2], Ok(vec![20]));
+        assert!(matches!(results[3], Err(RunError::Panicked(_))));
+        assert_eq!(results[4], Ok(vec![5]));
+    }
+}
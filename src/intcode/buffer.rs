@@ -0,0 +1,67 @@
+use std::collections::VecDeque;
+
+use super::{IO, Input, execute_op_code_with_custom_io};
+
+pub struct Buffer {
+    pub output: Vec<i64>,
+    input: VecDeque<i64>,
+    closed: bool,
+}
+
+impl Buffer {
+    pub fn new() -> Self {
+        Buffer {
+            output: Vec::new(),
+            input: VecDeque::new(),
+            closed: false
+        }
+    }
+
+    // The input is closed once the given values are consumed.
+    pub fn from(input: &[i64]) -> Self {
+        Buffer {
+            output: Vec::new(),
+            input: input.iter().copied().collect(),
+            closed: true
+        }
+    }
+
+    pub fn push(&mut self, value: i64) {
+        self.input.push_back(value)
+    }
+
+    pub fn close(&mut self) {
+        self.closed = true
+    }
+
+    // Number of input values not read yet.
+    pub fn nb_pending_inputs(&self) -> usize {
+        self.input.len()
+    }
+}
+
+impl Default for Buffer {
+    fn default() -> Self { Buffer::new() }
+}
+
+impl IO for Buffer {
+    fn read(&mut self) -> Input {
+        match self.input.pop_front() {
+            Some(value) => Input::Value(value),
+            None if self.closed => Input::Closed,
+            None => Input::Pending
+        }
+    }
+
+    fn write(&mut self, value: i64) {
+        self.output.push(value)
+    }
+}
+
+pub fn execute_op_code(code: &[i64], input: &[i64]) -> Vec<i64> {
+    let mut buffer = Buffer::from(input);
+    if let Err(error) = execute_op_code_with_custom_io(code, &mut buffer) {
+        panic!("Intcode error: {:?}", error)
+    }
+    buffer.output
+}
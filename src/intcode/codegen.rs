@@ -0,0 +1,415 @@
+use std::{collections::BTreeSet, convert::TryFrom};
+
+use super::{Mode, optimizer::{self, Analysis, Instruction, Options}};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    SelfModifyingCode, // The instructions may be written at runtime or some writes can't be analysed.
+    UnknownJumps, // Some jump targets computed at runtime can't be determined statically.
+}
+
+enum Operand {
+    Constant(i64),
+    Expression(String),
+}
+
+impl Operand {
+    fn to_code(&self) -> String {
+        match self {
+            Operand::Constant(value) if *value < 0 => format!("({})", value),
+            Operand::Constant(value) => value.to_string(),
+            Operand::Expression(expression) => expression.clone()
+        }
+    }
+}
+
+struct Generator<'a> {
+    code: &'a [i64],
+    analysis: Analysis,
+    leaders: BTreeSet<usize>, // First instruction of each block.
+    uses_relative_base: bool,
+}
+
+// Where a computed jump target is read from or where a value is written. Relative accesses are assumed to only touch the stack.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Location {
+    Cell(usize),
+    Stack,
+}
+
+// Return 'true' if all the jump targets computed at runtime are leaders: they are read from locations only written
+// with constants (like a return address pushed on the stack).
+fn jumps_are_resolved(code: &[i64], analysis: &Analysis, leaders: &BTreeSet<usize>) -> bool {
+    let location = |position: usize, mode: Mode| match mode {
+        Mode::Immediate => Location::Cell(position),
+        Mode::Position => Location::Cell(code.get(position).copied().unwrap_or(0) as usize),
+        Mode::Relative => Location::Stack
+    };
+
+    // The values written at runtime, 'None' if not constant.
+    let mut writes = Vec::<(Location, Option<i64>)>::new();
+    for (&address, instruction) in analysis.instructions.iter() {
+        match instruction.op {
+            1 | 2 | 7 | 8 => {
+                let a = analysis.constant(code, address + 1, instruction.modes[0]);
+                let b = analysis.constant(code, address + 2, instruction.modes[1]);
+                let value =
+                    match (instruction.op, a, b) {
+                        (1, Some(a), Some(b)) => a.checked_add(b),
+                        (2, Some(a), Some(b)) => a.checked_mul(b),
+                        (7, Some(a), Some(b)) => Some(if a < b { 1 } else { 0 }),
+                        (8, Some(a), Some(b)) => Some(if a == b { 1 } else { 0 }),
+                        _ => None
+                    };
+                writes.push((location(address + 3, instruction.modes[2]), value));
+            },
+            3 => writes.push((location(address + 1, instruction.modes[0]), None)),
+            _ => ()
+        }
+    }
+
+    analysis.instructions.iter().all(
+        |(&address, instruction)| {
+            if !(instruction.op == 5 || instruction.op == 6)
+                || analysis.condition(code, address, instruction) == Some(false)
+                || analysis.constant(code, address + 2, instruction.modes[1]).is_some() {
+                return true
+            }
+
+            let source = location(address + 2, instruction.modes[1]);
+            let initial = match source { Location::Cell(address) => code.get(address).copied().unwrap_or(0), Location::Stack => 0 };
+            let is_leader = |value: i64| usize::try_from(value).is_ok_and(|target| leaders.contains(&target));
+            is_leader(initial) && writes.iter().filter(|(location, _)| *location == source).all(|(_, value)| value.is_some_and(is_leader))
+        }
+    )
+}
+
+impl Generator<'_> {
+    // The address of the cell of the parameter 'p' of the instruction at 'address' as an expression of type 'usize'.
+    fn address(&self, address: usize, p: usize, mode: Mode) -> String {
+        let value = self.code.get(address + 1 + p).copied().unwrap_or(0);
+        match mode {
+            Mode::Immediate => (address + 1 + p).to_string(),
+            Mode::Position if value >= 0 => value.to_string(),
+            Mode::Position => format!("address(None, {})?", address),
+            Mode::Relative => format!("address(relative_base.checked_add({}), {})?", value, address)
+        }
+    }
+
+    fn operand(&self, address: usize, p: usize, instruction: &Instruction) -> Operand {
+        match self.analysis.constant(self.code, address + 1 + p, instruction.modes[p]) {
+            Some(value) => Operand::Constant(value),
+            None => Operand::Expression(format!("memory.get({})", self.address(address, p, instruction.modes[p])))
+        }
+    }
+
+    fn write(&self, address: usize, p: usize, instruction: &Instruction, value: &str) -> String {
+        if instruction.modes[p] == Mode::Immediate {
+            format!("return Err(intcode::Error::WriteInImmediateMode {{ cursor: {} }});", address)
+        } else {
+            format!("memory.set({}, {});", self.address(address, p, instruction.modes[p]), value)
+        }
+    }
+
+    // The context given to the IO, 'executed' is the number of instructions executed in the block before this one.
+    fn context(&self, address: usize, executed: usize) -> String {
+        format!(
+            "&intcode::Context {{ cursor: {}, relative_base: {}, instruction_count: instruction_count + {}, memory: &memory }}",
+            address, if self.uses_relative_base { "relative_base" } else { "0" }, executed
+        )
+    }
+
+    // Return the lines of the instruction and 'true' if the execution continues to the next instruction.
+    fn instruction(&self, address: usize, instruction: &Instruction, executed: usize) -> (Vec<String>, bool) {
+        let next = address + instruction.len;
+        match instruction.op {
+            1 | 2 | 7 | 8 => {
+                let (a, b) = (self.operand(address, 0, instruction), self.operand(address, 1, instruction));
+                // An overflow is left to the execution, like in the machine.
+                let value =
+                    match (instruction.op, &a, &b) {
+                        (1, Operand::Constant(a), Operand::Constant(b)) if a.checked_add(*b).is_some() => (a + b).to_string(),
+                        (2, Operand::Constant(a), Operand::Constant(b)) if a.checked_mul(*b).is_some() => (a * b).to_string(),
+                        (7, Operand::Constant(a), Operand::Constant(b)) => (if a < b { 1 } else { 0 }).to_string(),
+                        (8, Operand::Constant(a), Operand::Constant(b)) => (if a == b { 1 } else { 0 }).to_string(),
+                        (1, _, _) => format!("{} + {}", a.to_code(), b.to_code()),
+                        (2, _, _) => format!("{} * {}", a.to_code(), b.to_code()),
+                        (7, _, _) => format!("if {} < {} {{ 1 }} else {{ 0 }}", a.to_code(), b.to_code()),
+                        _ => format!("if {} == {} {{ 1 }} else {{ 0 }}", a.to_code(), b.to_code())
+                    };
+                (vec![format!("let value = {};", value), self.write(address, 2, instruction, "value")], true)
+            },
+
+            3 => (
+                vec![
+                    String::from("let value ="),
+                    format!("    match io.read_with_context({}) {{", self.context(address, executed)),
+                    String::from("        intcode::Input::Value(value) => value,"),
+                    String::from("        intcode::Input::Pending => return Ok(intcode::Status::WaitingForInput),"),
+                    format!("        intcode::Input::Closed => return Err(intcode::Error::InputClosed {{ cursor: {} }}),", address),
+                    String::from("    };"),
+                    self.write(address, 0, instruction, "value"),
+                    String::from("if io.halt() { io.finished(); return Ok(intcode::Status::Halted); }")
+                ],
+                true
+            ),
+
+            4 => (
+                vec![
+                    format!("io.write_with_context({}, {});", self.operand(address, 0, instruction).to_code(), self.context(address, executed)),
+                    String::from("if io.halt() { io.finished(); return Ok(intcode::Status::Halted); }")
+                ],
+                true
+            ),
+
+            5 | 6 => {
+                let target =
+                    match self.operand(address, 1, instruction) {
+                        Operand::Constant(target) if target >= 0 => target.to_string(),
+                        Operand::Constant(_) => format!("address(None, {})?", address),
+                        expression => format!("address(Some({}), {})?", expression.to_code(), address)
+                    };
+                let line =
+                    match self.analysis.condition(self.code, address, instruction) {
+                        Some(true) => format!("cursor = {};", target),
+                        Some(false) => format!("cursor = {};", next),
+                        None => format!("cursor = if {} {} 0 {{ {} }} else {{ {} }};", self.operand(address, 0, instruction).to_code(), if instruction.op == 5 { "!=" } else { "==" }, target, next)
+                    };
+                (vec![line], false)
+            },
+
+            9 => (vec![format!("relative_base = relative_base.checked_add({}).ok_or(intcode::Error::InvalidAddress {{ cursor: {} }})?;", self.operand(address, 0, instruction).to_code(), address)], true),
+
+            _ => (vec![String::from("io.finished();"), String::from("return Ok(intcode::Status::Halted);")], false)
+        }
+    }
+
+    // The instruction count is updated when leaving the block.
+    fn block(&self, leader: usize) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut address = leader;
+        let mut executed = 0;
+        loop {
+            let instruction = &self.analysis.instructions[&address];
+            let (instruction_lines, continues) = self.instruction(address, instruction, executed);
+            executed += 1;
+            if !continues {
+                if instruction.op != 99 {
+                    lines.push(format!("instruction_count += {};", executed));
+                }
+                lines.extend(instruction_lines);
+                return lines
+            }
+            lines.extend(instruction_lines);
+
+            address += instruction.len;
+            if self.leaders.contains(&address) || !self.analysis.instructions.contains_key(&address) {
+                lines.push(format!("instruction_count += {};", executed));
+                lines.push(format!("cursor = {};", address));
+                return lines
+            }
+        }
+    }
+}
+
+// Emit the source of a Rust function equivalent to 'execute_op_code_with_custom_io' with the given program.
+// The generated function uses the module 'intcode' which must be in scope where it is included.
+// The instructions must not be written at runtime, this is verified statically. When some jump targets are
+// computed at runtime, the constants pointing after a jump (like return addresses) are considered as possible targets,
+// the generation fails if a computed target may be something else.
+pub fn generate(code: &[i64], function_name: &str, options: Options) -> Result<String, Error> {
+    let mut entries = vec![0];
+    let analysis =
+        loop {
+            let analysis = optimizer::analyse_from(code, options, &entries).ok_or(Error::SelfModifyingCode)?;
+            if !analysis.unknown_jumps { break analysis }
+
+            // A return address is usually stored as the sum of two constants and points after a jump (the call).
+            let after_jumps: BTreeSet<usize> = analysis.instructions.iter().filter(|(_, instruction)| instruction.op == 5 || instruction.op == 6).map(|(address, instruction)| address + instruction.len).collect();
+            let mut new_entries = entries.clone();
+            for (&address, instruction) in analysis.instructions.iter().filter(|(_, instruction)| instruction.op == 1 || instruction.op == 2) {
+                let a = analysis.constant(code, address + 1, instruction.modes[0]);
+                let b = analysis.constant(code, address + 2, instruction.modes[1]);
+                if let Some(value) = a.zip(b).and_then(|(a, b)| if instruction.op == 1 { a.checked_add(b) } else { a.checked_mul(b) }) {
+                    if value >= 0 && after_jumps.contains(&(value as usize)) && !new_entries.contains(&(value as usize)) {
+                        new_entries.push(value as usize);
+                    }
+                }
+            }
+            if new_entries.len() == entries.len() { break analysis }
+            entries = new_entries;
+        };
+
+    if analysis.instructions.iter().any(|(&address, instruction)| analysis.is_written(address, instruction.len)) {
+        return Err(Error::SelfModifyingCode)
+    }
+
+    let mut leaders: BTreeSet<usize> = entries.iter().copied().collect();
+    let mut uses_relative_base = false;
+    for (&address, instruction) in analysis.instructions.iter() {
+        uses_relative_base |= instruction.op == 9 || instruction.modes.contains(&Mode::Relative);
+        if instruction.op == 5 || instruction.op == 6 {
+            leaders.insert(address + instruction.len);
+            if let Some(target) = analysis.constant(code, address + 2, instruction.modes[1]) {
+                leaders.insert(target as usize);
+            }
+        }
+    }
+
+    leaders.retain(|address| analysis.instructions.contains_key(address)); // The others are invalid.
+
+    if analysis.unknown_jumps && !jumps_are_resolved(code, &analysis, &leaders) {
+        return Err(Error::UnknownJumps)
+    }
+
+    let generator = Generator { code, analysis, leaders, uses_relative_base };
+
+    let mut lines = vec![
+        String::from("// Generated from an Intcode program by 'intcode::codegen::generate'."),
+        String::from("#[allow(unused_mut, unused_variables, unused_parens, unused_assignments, unreachable_code, dead_code, arithmetic_overflow, clippy::all)]"),
+        format!("pub fn {}(io: &mut dyn intcode::IO) -> Result<intcode::Status, intcode::Error> {{", function_name),
+        String::from("    // A negative or overflowed address is an error of the instruction at 'cursor'."),
+        String::from("    fn address(address: Option<i64>, cursor: usize) -> Result<usize, intcode::Error> {"),
+        String::from("        match address {"),
+        String::from("            Some(address) if address >= 0 => Ok(address as usize),"),
+        String::from("            _ => Err(intcode::Error::InvalidAddress { cursor })"),
+        String::from("        }"),
+        String::from("    }"),
+        String::new(),
+        format!("    let mut memory = intcode::memory::Memory::from(&[{}]);", code.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(", ")),
+    ];
+    if generator.uses_relative_base {
+        lines.push(String::from("    let mut relative_base: i64 = 0;"));
+    }
+    lines.push(String::from("    let mut cursor: usize = 0;"));
+    lines.push(String::from("    let mut instruction_count: u64 = 0; // Updated at the end of each block."));
+    lines.push(String::new());
+    lines.push(String::from("    if io.halt() { io.finished(); return Ok(intcode::Status::Halted); }"));
+    lines.push(String::new());
+    lines.push(String::from("    loop {"));
+    lines.push(String::from("        match cursor {"));
+    for &leader in generator.leaders.iter() {
+        lines.push(format!("            {} => {{", leader));
+        lines.extend(generator.block(leader).into_iter().map(|line| format!("                {}", line)));
+        lines.push(String::from("            }"));
+    }
+    lines.push(String::from("            _ => return Err(intcode::Error::UnknownOpCode { cursor, op_code: memory.get(cursor) }),"));
+    lines.push(String::from("        }"));
+    lines.push(String::from("    }"));
+    lines.push(String::from("}"));
+
+    Ok(lines.join("\n") + "\n")
+}
+
+// Output 1 + 2 + ... + n where n is the input.
+pub const SUM_TO_N: [i64; 23] = [3,100,1101,0,0,101,1006,100,20,1,101,100,101,101,-1,100,100,1105,1,6,4,101,99];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{self as intcode, Buffer, execute_op_code, execute_op_code_with_custom_io};
+
+    include!("codegen/sum_to_n.rs");
+
+    #[test]
+    fn generated_source_is_up_to_date() {
+        assert_eq!(generate(&SUM_TO_N, "sum_to_n", Options::default()), Ok(String::from(include_str!("codegen/sum_to_n.rs"))));
+    }
+
+    #[test]
+    fn generated_function_is_equivalent() {
+        for n in [0, 1, 10, 1000] {
+            let mut buffer = Buffer::from(&[n]);
+            assert_eq!(sum_to_n(&mut buffer), Ok(intcode::Status::Halted));
+            assert_eq!(buffer.output, execute_op_code(&SUM_TO_N, &[n]));
+        }
+
+        let mut interpreted = Buffer::from(&[]);
+        let mut generated = Buffer::from(&[]);
+        assert_eq!(sum_to_n(&mut generated), execute_op_code_with_custom_io(&SUM_TO_N, &mut interpreted));
+    }
+
+    // Only implement the context variants and record what the context shows.
+    struct ContextRecorder {
+        input: i64,
+        contexts: Vec<(usize, i64, u64, i64)>, // Cursor, relative base, instruction count and cell 100.
+        output: Vec<i64>,
+    }
+
+    impl intcode::IO for ContextRecorder {
+        fn read(&mut self) -> intcode::Input { unreachable!() }
+        fn write(&mut self, _value: i64) { unreachable!() }
+
+        fn read_with_context(&mut self, context: &intcode::Context) -> intcode::Input {
+            self.contexts.push((context.cursor, context.relative_base, context.instruction_count, context.memory.get(100)));
+            intcode::Input::Value(self.input)
+        }
+
+        fn write_with_context(&mut self, value: i64, context: &intcode::Context) {
+            self.contexts.push((context.cursor, context.relative_base, context.instruction_count, context.memory.get(100)));
+            self.output.push(value);
+        }
+    }
+
+    #[test]
+    fn context_in_callbacks() {
+        let mut interpreted = ContextRecorder { input: 10, contexts: Vec::new(), output: Vec::new() };
+        let mut generated = ContextRecorder { input: 10, contexts: Vec::new(), output: Vec::new() };
+        execute_op_code_with_custom_io(&SUM_TO_N, &mut interpreted).unwrap();
+        sum_to_n(&mut generated).unwrap();
+        assert_eq!(generated.output, vec![55]);
+        assert_eq!(generated.contexts, interpreted.contexts);
+        assert_eq!(generated.contexts[1], (20, 0, 43, 0));
+    }
+
+    #[test]
+    fn dynamic_jumps() {
+        // Call a function doubling [100] twice with the return address on the stack.
+        let c = [109,50,1101,0,3,100,21101,13,0,0,1105,1,24,21101,20,0,0,1105,1,24,4,100,99,0,1002,100,2,100,2105,1,0];
+        assert_eq!(execute_op_code(&c, &[]), vec![12]);
+        let source = generate(&c, "double", Options { assume_stack_after_program: true }).unwrap();
+        assert!(source.contains("            13 => {"));
+        assert!(source.contains("            20 => {"));
+        assert!(source.contains("cursor = address(Some(memory.get(address(relative_base.checked_add(0), 28)?)), 28)?;"));
+        assert_eq!(generate(&c, "double", Options::default()), Err(Error::SelfModifyingCode));
+    }
+
+    #[test]
+    fn invalid_addresses() {
+        let source = generate(&[1101,1,1,-5,99], "f", Options::default()).unwrap();
+        assert!(source.contains("memory.set(address(None, 0)?, value);"));
+        let source = generate(&[11101,1,1,0,99], "f", Options::default()).unwrap();
+        assert!(source.contains("return Err(intcode::Error::WriteInImmediateMode { cursor: 0 });"));
+        let source = generate(&[109,-3,204,1,99], "f", Options::default()).unwrap();
+        assert!(source.contains("relative_base = relative_base.checked_add((-3)).ok_or(intcode::Error::InvalidAddress { cursor: 0 })?;"));
+        assert!(source.contains("memory.get(address(relative_base.checked_add(1), 2)?)"));
+        let source = generate(&[1105,1,-3], "f", Options::default()).unwrap();
+        assert!(source.contains("cursor = address(None, 0)?;"));
+    }
+
+    #[test]
+    fn overflowing_constants() {
+        // The sum is computed at runtime, the product of two constants is folded.
+        let source = generate(&[1101,9_223_372_036_854_775_807,1,11,1102,3,4,11,4,11,99,0], "f", Options::default()).unwrap();
+        assert!(source.contains("let value = 9223372036854775807 + 1;"));
+        assert!(source.contains("let value = 12;"));
+    }
+
+    #[test]
+    fn refuse_unknown_jumps() {
+        // Jump to the input: 7 outputs 2, an instruction which isn't the start of a block.
+        let c = [3,100,106,0,100,104,1,104,2,99];
+        assert_eq!(execute_op_code(&c, &[7]), vec![2]);
+        assert_eq!(generate(&c, "f", Options::default()), Err(Error::UnknownJumps));
+
+        // Jump to a computed value stored on the stack.
+        let c = [109,50,3,100,20101,0,100,0,2106,0,0,99];
+        assert_eq!(generate(&c, "f", Options { assume_stack_after_program: true }), Err(Error::UnknownJumps));
+    }
+
+    #[test]
+    fn refuse_self_modifying_code() {
+        assert_eq!(generate(&[1101,0,99,4,1101,1,1,0,99], "f", Options::default()), Err(Error::SelfModifyingCode));
+        assert_eq!(generate(&[3,3,1105,0,0,99], "f", Options::default()), Err(Error::SelfModifyingCode));
+    }
+}
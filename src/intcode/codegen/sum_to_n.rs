@@ -0,0 +1,55 @@
+// Generated from an Intcode program by 'intcode::codegen::generate'.
+#[allow(unused_mut, unused_variables, unused_parens, unused_assignments, unreachable_code, dead_code, arithmetic_overflow, clippy::all)]
+pub fn sum_to_n(io: &mut dyn intcode::IO) -> Result<intcode::Status, intcode::Error> {
+    // A negative or overflowed address is an error of the instruction at 'cursor'.
+    fn address(address: Option<i64>, cursor: usize) -> Result<usize, intcode::Error> {
+        match address {
+            Some(address) if address >= 0 => Ok(address as usize),
+            _ => Err(intcode::Error::InvalidAddress { cursor })
+        }
+    }
+
+    let mut memory = intcode::memory::Memory::from(&[3, 100, 1101, 0, 0, 101, 1006, 100, 20, 1, 101, 100, 101, 101, -1, 100, 100, 1105, 1, 6, 4, 101, 99]);
+    let mut cursor: usize = 0;
+    let mut instruction_count: u64 = 0; // Updated at the end of each block.
+
+    if io.halt() { io.finished(); return Ok(intcode::Status::Halted); }
+
+    loop {
+        match cursor {
+            0 => {
+                let value =
+                    match io.read_with_context(&intcode::Context { cursor: 0, relative_base: 0, instruction_count: instruction_count + 0, memory: &memory }) {
+                        intcode::Input::Value(value) => value,
+                        intcode::Input::Pending => return Ok(intcode::Status::WaitingForInput),
+                        intcode::Input::Closed => return Err(intcode::Error::InputClosed { cursor: 0 }),
+                    };
+                memory.set(100, value);
+                if io.halt() { io.finished(); return Ok(intcode::Status::Halted); }
+                let value = 0;
+                memory.set(101, value);
+                instruction_count += 2;
+                cursor = 6;
+            }
+            6 => {
+                instruction_count += 1;
+                cursor = if memory.get(100) == 0 { 20 } else { 9 };
+            }
+            9 => {
+                let value = memory.get(101) + memory.get(100);
+                memory.set(101, value);
+                let value = (-1) + memory.get(100);
+                memory.set(100, value);
+                instruction_count += 3;
+                cursor = 6;
+            }
+            20 => {
+                io.write_with_context(memory.get(101), &intcode::Context { cursor: 20, relative_base: 0, instruction_count: instruction_count + 0, memory: &memory });
+                if io.halt() { io.finished(); return Ok(intcode::Status::Halted); }
+                io.finished();
+                return Ok(intcode::Status::Halted);
+            }
+            _ => return Err(intcode::Error::UnknownOpCode { cursor, op_code: memory.get(cursor) }),
+        }
+    }
+}
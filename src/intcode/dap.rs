@@ -0,0 +1,559 @@
+// A Debug Adapter Protocol server (https://microsoft.github.io/debug-adapter-protocol/) driving an Intcode machine.
+// The messages are exchanged through any reader and writer, usually stdin and stdout.
+// The source shown by the editor is the disassembly of the program at launch, breakpoints can be set on its lines
+// or on instruction addresses. Values can be given to the program with the expression "input <values>" in the
+// debug console, the execution stops when the program waits for an input.
+
+use std::{collections::BTreeSet, convert::TryFrom, fs, io::{self, BufRead, Write}, mem};
+
+use serde_json::{json, Value};
+
+use super::{Buffer, Machine, Status, disassembler::{self, Line}};
+
+const SOURCE_REFERENCE: i64 = 1;
+const THREAD_ID: i64 = 1;
+
+const REGISTERS_REFERENCE: i64 = 1;
+const RELATIVE_BASE_REFERENCE: i64 = 2;
+const MEMORY_REFERENCE: i64 = 3;
+const FIRST_RANGE_REFERENCE: i64 = 1000; // The memory range 'n' has the reference 'FIRST_RANGE_REFERENCE + n'.
+const RANGE_SIZE: usize = 32;
+const NB_CELLS_AROUND_RELATIVE_BASE: i64 = 8; // Shown before and after the relative base.
+
+struct Program {
+    name: String,
+    machine: Machine,
+    buffer: Buffer,
+    lines: Vec<Line>,
+    stop_on_entry: bool,
+    ascii: bool, // Output values are shown as characters.
+    terminated: bool,
+}
+
+enum Stop {
+    Entry,
+    Step,
+    Breakpoint,
+    Input,
+    Halted,
+    Error(super::Error),
+}
+
+type Event = (&'static str, Value);
+
+#[derive(Default)]
+pub struct Server {
+    seq: i64,
+    program: Option<Program>,
+    line_breakpoints: Vec<i64>, // Lines of the disassembly, they are resolved to addresses when the program is launched.
+    instruction_breakpoints: Vec<usize>,
+    configured: bool,
+    started: bool,
+    disconnected: bool,
+}
+
+fn parse_values(text: &str) -> Result<Vec<i64>, String> {
+    text.split(',').map(str::trim).filter(|value| !value.is_empty()).map(|value| value.parse::<i64>().map_err(|error| format!("Invalid value \"{}\": {}", value, error))).collect()
+}
+
+fn values_argument(argument: &Value) -> Result<Vec<i64>, String> {
+    match argument {
+        Value::Null => Ok(Vec::new()),
+        Value::String(text) => parse_values(text),
+        Value::Array(values) => values.iter().map(|value| value.as_i64().ok_or(format!("Invalid value: {}", value))).collect(),
+        _ => Err(format!("Invalid values: {}", argument))
+    }
+}
+
+fn variable(name: String, value: String, variables_reference: i64) -> Value {
+    json!({ "name": name, "value": value, "variablesReference": variables_reference })
+}
+
+impl Server {
+    pub fn new() -> Self { Server::default() }
+
+    pub fn is_disconnected(&self) -> bool { self.disconnected }
+
+    fn next_seq(&mut self) -> i64 {
+        self.seq += 1;
+        self.seq
+    }
+
+    // Handle a request and return the response followed by the events it has triggered.
+    pub fn handle(&mut self, request: &Value) -> Vec<Value> {
+        let command = request["command"].as_str().unwrap_or("");
+        let arguments = &request["arguments"];
+        let mut events = Vec::new();
+
+        let result =
+            match command {
+                "initialize" => {
+                    events.push(("initialized", json!({})));
+                    Ok(json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsInstructionBreakpoints": true,
+                        "supportsDisassembleRequest": true,
+                        "supportsEvaluateForHovers": true,
+                    }))
+                },
+                "launch" => {
+                    let result = self.launch(arguments);
+                    events.extend(self.start());
+                    result
+                },
+                "setBreakpoints" => self.set_breakpoints(arguments),
+                "setInstructionBreakpoints" => self.set_instruction_breakpoints(arguments),
+                "setExceptionBreakpoints" => Ok(json!({ "breakpoints": [] })),
+                "configurationDone" => {
+                    self.configured = true;
+                    events.extend(self.start());
+                    Ok(Value::Null)
+                },
+                "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "intcode" }] })),
+                "stackTrace" => self.stack_trace(),
+                "scopes" => Ok(self.scopes()),
+                "variables" => self.variables(arguments),
+                "source" => self.source(),
+                "disassemble" => self.disassemble(arguments),
+                "evaluate" => self.evaluate(arguments),
+                "continue" => self.resume(false, &mut events).map(|_| json!({ "allThreadsContinued": true })),
+                "next" | "stepIn" | "stepOut" => self.resume(true, &mut events),
+                "pause" => {
+                    // The program is executed synchronously so it is always stopped when a request is handled.
+                    events.push(("stopped", json!({ "reason": "pause", "threadId": THREAD_ID })));
+                    Ok(Value::Null)
+                },
+                "disconnect" | "terminate" => {
+                    self.disconnected = true;
+                    Ok(Value::Null)
+                },
+                _ => Err(format!("Unsupported command: \"{}\"", command))
+            };
+
+        let mut response = json!({
+            "seq": self.next_seq(),
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": command,
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => (),
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = Value::String(message)
+        }
+
+        let mut messages = vec![response];
+        for (event, body) in events {
+            messages.push(json!({ "seq": self.next_seq(), "type": "event", "event": event, "body": body }));
+        }
+        messages
+    }
+
+    fn program(&self) -> Result<&Program, String> {
+        self.program.as_ref().ok_or_else(|| String::from("No program launched"))
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+        let (name, code) =
+            match arguments["program"].as_str() {
+                Some(path) => (String::from(path), parse_values(&fs::read_to_string(path).map_err(|error| format!("Unable to read \"{}\": {}", path, error))?)?),
+                None => (String::from("program"), values_argument(&arguments["code"])?)
+            };
+
+        let mut buffer = Buffer::new();
+        for value in values_argument(&arguments["input"])? {
+            buffer.push(value);
+        }
+
+        self.program = Some(Program {
+            name,
+            lines: disassembler::disassemble(&code),
+            machine: Machine::new(&code),
+            buffer,
+            stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false),
+            ascii: arguments["ascii"].as_bool().unwrap_or(false),
+            terminated: false,
+        });
+        Ok(Value::Null)
+    }
+
+    // Start the execution once the program is launched and the configuration is done.
+    // The events are sent after the response of the current request.
+    fn start(&mut self) -> Vec<Event> {
+        if self.started || !self.configured || self.program.is_none() { return Vec::new() }
+        self.started = true;
+        if self.program.as_ref().unwrap().stop_on_entry {
+            vec![self.stop_event(Stop::Entry)]
+        } else {
+            self.run(false)
+        }
+    }
+
+    fn breakpoint_addresses(&self) -> BTreeSet<usize> {
+        let mut addresses: BTreeSet<usize> = self.instruction_breakpoints.iter().copied().collect();
+        if let Some(program) = self.program.as_ref() {
+            addresses.extend(self.line_breakpoints.iter().filter_map(|line| usize::try_from(*line).ok().and_then(|line| program.lines.get(line.checked_sub(1)?)).map(|line| line.address)));
+        }
+        addresses
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let lines: Vec<i64> = arguments["breakpoints"].as_array().map(|breakpoints| breakpoints.iter().filter_map(|breakpoint| breakpoint["line"].as_i64()).collect()).unwrap_or_default();
+        let nb_lines = self.program.as_ref().map(|program| program.lines.len() as i64);
+        let breakpoints: Vec<Value> = lines.iter().map(|line| json!({ "verified": *line >= 1 && nb_lines.map_or(true, |n| *line <= n), "line": line })).collect();
+        self.line_breakpoints = lines;
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn set_instruction_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let mut breakpoints = Vec::new();
+        self.instruction_breakpoints.clear();
+        for breakpoint in arguments["breakpoints"].as_array().into_iter().flatten() {
+            let address = breakpoint["instructionReference"].as_str().and_then(|reference| reference.parse::<i64>().ok()).and_then(|address| address.checked_add(breakpoint["offset"].as_i64().unwrap_or(0)));
+            match address {
+                Some(address) if address >= 0 => {
+                    self.instruction_breakpoints.push(address as usize);
+                    breakpoints.push(json!({ "verified": true, "instructionReference": address.to_string() }));
+                },
+                _ => breakpoints.push(json!({ "verified": false, "message": "Invalid address" }))
+            }
+        }
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn resume(&mut self, single_step: bool, events: &mut Vec<Event>) -> Result<Value, String> {
+        let program = self.program()?;
+        if program.terminated { return Err(String::from("The program has terminated")) }
+        events.extend(self.run(single_step));
+        Ok(Value::Null)
+    }
+
+    // Execute the program until a breakpoint is reached, it halts or waits for an input.
+    // The first instruction is always executed to be able to resume from a breakpoint.
+    fn run(&mut self, single_step: bool) -> Vec<Event> {
+        let breakpoints = self.breakpoint_addresses();
+        let program = self.program.as_mut().unwrap();
+        let mut first = true;
+        let stop =
+            loop {
+                if !first && breakpoints.contains(&program.machine.cursor()) { break Stop::Breakpoint }
+                first = false;
+                match program.machine.step(&mut program.buffer) {
+                    Ok(None) if single_step => break Stop::Step,
+                    Ok(None) => (),
+                    Ok(Some(Status::Halted)) => break Stop::Halted,
+                    Ok(Some(Status::WaitingForInput)) => break Stop::Input,
+                    Err(error) => break Stop::Error(error)
+                }
+            };
+
+        let mut events: Vec<Event> =
+            mem::take(&mut program.buffer.output).into_iter().map(
+                |value| {
+                    let output = if program.ascii && (0 .. 128).contains(&value) { (value as u8 as char).to_string() } else { format!("{}\n", value) };
+                    ("output", json!({ "category": "stdout", "output": output }))
+                }
+            ).collect();
+        events.push(self.stop_event(stop));
+        if self.program.as_ref().unwrap().terminated {
+            events.push(("terminated", json!({})));
+        }
+        events
+    }
+
+    fn stop_event(&mut self, stop: Stop) -> Event {
+        let program = self.program.as_mut().unwrap();
+        let stopped = |reason: &str, description: &str| ("stopped", json!({ "reason": reason, "description": description, "threadId": THREAD_ID, "allThreadsStopped": true }));
+        match stop {
+            Stop::Entry => stopped("entry", "Entry"),
+            Stop::Step => stopped("step", "Step"),
+            Stop::Breakpoint => stopped("breakpoint", "Breakpoint"),
+            Stop::Input => stopped("pause", "Waiting for input, use \"input <values>\" in the debug console"),
+            Stop::Halted => {
+                program.terminated = true;
+                ("exited", json!({ "exitCode": 0 }))
+            },
+            Stop::Error(error) => {
+                program.terminated = true;
+                ("output", json!({ "category": "stderr", "output": format!("Intcode error: {:?}\n", error) }))
+            }
+        }
+    }
+
+    fn stack_trace(&self) -> Result<Value, String> {
+        let program = self.program()?;
+        let cursor = program.machine.cursor();
+        let (line, name) =
+            match disassembler::line_of(&program.lines, cursor) {
+                Some(index) => (index as i64 + 1, program.lines[index].text.clone()),
+                None => (0, format!("{} (outside the program)", cursor))
+            };
+        Ok(json!({
+            "stackFrames": [{
+                "id": 1,
+                "name": name,
+                "line": line,
+                "column": 1,
+                "source": { "name": format!("{} (disassembly)", program.name), "sourceReference": SOURCE_REFERENCE },
+                "instructionPointerReference": cursor.to_string(),
+            }],
+            "totalFrames": 1,
+        }))
+    }
+
+    fn scopes(&self) -> Value {
+        json!({
+            "scopes": [
+                { "name": "Registers", "variablesReference": REGISTERS_REFERENCE, "expensive": false },
+                { "name": "Relative base", "variablesReference": RELATIVE_BASE_REFERENCE, "expensive": false },
+                { "name": "Memory", "variablesReference": MEMORY_REFERENCE, "expensive": true },
+            ]
+        })
+    }
+
+    fn cells(program: &Program, from: usize, to: usize) -> Vec<Value> {
+        (from .. to).map(|address| variable(format!("[{}]", address), program.machine.memory().get(address).to_string(), 0)).collect()
+    }
+
+    fn variables(&self, arguments: &Value) -> Result<Value, String> {
+        let program = self.program()?;
+        let machine = &program.machine;
+        let variables =
+            match arguments["variablesReference"].as_i64().unwrap_or(0) {
+                REGISTERS_REFERENCE => vec![
+                    variable(String::from("cursor"), machine.cursor().to_string(), 0),
+                    variable(String::from("relative base"), machine.relative_base().to_string(), 0),
+                    variable(String::from("instruction count"), machine.instruction_count().to_string(), 0),
+                    variable(String::from("memory size"), machine.memory().len().to_string(), 0),
+                ],
+                RELATIVE_BASE_REFERENCE => {
+                    let relative_base = machine.relative_base();
+                    let from = relative_base.saturating_sub(NB_CELLS_AROUND_RELATIVE_BASE).max(0) as usize;
+                    let to = relative_base.saturating_add(NB_CELLS_AROUND_RELATIVE_BASE + 1).max(0) as usize;
+                    Server::cells(program, from, to)
+                },
+                MEMORY_REFERENCE => {
+                    let nb_ranges = (machine.memory().len() + RANGE_SIZE - 1) / RANGE_SIZE;
+                    (0 .. nb_ranges).map(|n| variable(format!("{} .. {}", n * RANGE_SIZE, (n + 1) * RANGE_SIZE - 1), String::new(), FIRST_RANGE_REFERENCE + n as i64)).collect()
+                },
+                reference if reference >= FIRST_RANGE_REFERENCE => {
+                    let from = usize::try_from(reference - FIRST_RANGE_REFERENCE).ok().and_then(|n| n.checked_mul(RANGE_SIZE)).ok_or_else(|| format!("Invalid variables reference: {}", reference))?;
+                    Server::cells(program, from, from.saturating_add(RANGE_SIZE).min(machine.memory().len()))
+                },
+                reference => return Err(format!("Unknown variables reference: {}", reference))
+            };
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn source(&self) -> Result<Value, String> {
+        let program = self.program()?;
+        Ok(json!({ "content": disassembler::to_string(&program.lines), "mimeType": "text/x-intcode" }))
+    }
+
+    fn disassemble(&self, arguments: &Value) -> Result<Value, String> {
+        let program = self.program()?;
+        let address =
+            arguments["memoryReference"].as_str().and_then(|reference| reference.parse::<i64>().ok())
+                .and_then(|address| address.checked_add(arguments["offset"].as_i64().unwrap_or(0)))
+                .ok_or("Invalid memory reference")?;
+        let first =
+            disassembler::line_of(&program.lines, address.max(0) as usize).map_or(program.lines.len() as i64, |index| index as i64)
+                .checked_add(arguments["instructionOffset"].as_i64().unwrap_or(0))
+                .ok_or("Invalid instruction offset")?;
+        let instructions: Vec<Value> =
+            (0 .. arguments["instructionCount"].as_i64().unwrap_or(0)).map(
+                |i| {
+                    match first.checked_add(i).and_then(|index| usize::try_from(index).ok()).and_then(|index| program.lines.get(index).map(|line| (index, line))) {
+                        Some((index, line)) => json!({ "address": line.address.to_string(), "instruction": line.text, "line": index + 1 }),
+                        None => json!({ "address": "-1", "instruction": "", "presentationHint": "invalid" })
+                    }
+                }
+            ).collect();
+        Ok(json!({ "instructions": instructions }))
+    }
+
+    // Supported expressions: "input <values separated by commas>", "[<address>]", "cursor" and "rb".
+    fn evaluate(&mut self, arguments: &Value) -> Result<Value, String> {
+        let expression = arguments["expression"].as_str().unwrap_or("").trim();
+        let program = self.program.as_mut().ok_or_else(|| String::from("No program launched"))?;
+        let result =
+            if let Some(values) = expression.strip_prefix("input") {
+                let values = parse_values(values)?;
+                for value in values.iter() {
+                    program.buffer.push(*value);
+                }
+                format!("{} value(s) added to the input", values.len())
+            } else if expression.starts_with('[') && expression.ends_with(']') {
+                let address = expression[1 .. expression.len() - 1].trim().parse::<usize>().map_err(|error| format!("Invalid address: {}", error))?;
+                program.machine.memory().get(address).to_string()
+            } else if expression == "cursor" {
+                program.machine.cursor().to_string()
+            } else if expression == "rb" {
+                program.machine.relative_base().to_string()
+            } else {
+                return Err(format!("Unknown expression: \"{}\"", expression))
+            };
+        Ok(json!({ "result": result, "variablesReference": 0 }))
+    }
+}
+
+// Read a message framed by a "Content-Length" header, return 'None' at the end of the stream.
+pub fn read_message(reader: &mut dyn BufRead) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 { return Ok(None) }
+        let header = header.trim();
+        if header.is_empty() {
+            if content_length.is_some() { break } else { continue }
+        }
+        if let Some(length) = header.strip_prefix("Content-Length:") {
+            content_length = Some(length.trim().parse::<usize>().map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?);
+        }
+    }
+
+    let mut content = vec![0; content_length.unwrap()];
+    reader.read_exact(&mut content)?;
+    serde_json::from_slice(&content).map(Some).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+pub fn write_message(writer: &mut dyn Write, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", content.len(), content)?;
+    writer.flush()
+}
+
+// Serve the requests until the client disconnects or closes the stream.
+pub fn serve(reader: &mut dyn BufRead, writer: &mut dyn Write) -> io::Result<()> {
+    let mut server = Server::new();
+    while let Some(request) = read_message(reader)? {
+        for message in server.handle(&request) {
+            write_message(writer, &message)?;
+        }
+        if server.is_disconnected() { break }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Output the double of the input.
+    const DOUBLE: [i64; 11] = [3,9,1002,9,2,10,4,10,99,0,0];
+
+    fn request(server: &mut Server, command: &str, arguments: Value) -> Vec<Value> {
+        let messages = server.handle(&json!({ "seq": 1, "type": "request", "command": command, "arguments": arguments }));
+        assert_eq!(messages[0]["type"], "response");
+        assert_eq!(messages[0]["success"], true, "{}", messages[0]);
+        messages
+    }
+
+    fn events(messages: &[Value]) -> Vec<String> {
+        messages[1 ..].iter().map(|message| format!("{} {}", message["event"].as_str().unwrap(), message["body"]["reason"].as_str().or_else(|| message["body"]["output"].as_str()).unwrap_or(""))).collect()
+    }
+
+    #[test]
+    fn debug_session() {
+        let mut server = Server::new();
+        assert_eq!(events(&request(&mut server, "initialize", json!({}))), vec!["initialized "]);
+        request(&mut server, "launch", json!({ "code": DOUBLE, "stopOnEntry": true }));
+
+        let breakpoints = request(&mut server, "setBreakpoints", json!({ "source": { "sourceReference": 1 }, "breakpoints": [{ "line": 3 }, { "line": 42 }] }));
+        assert_eq!(breakpoints[0]["body"]["breakpoints"][0]["verified"], true);
+        assert_eq!(breakpoints[0]["body"]["breakpoints"][1]["verified"], false);
+
+        assert_eq!(events(&request(&mut server, "configurationDone", json!({}))), vec!["stopped entry"]);
+        assert_eq!(events(&request(&mut server, "continue", json!({ "threadId": 1 }))), vec!["stopped pause"]);
+        assert_eq!(request(&mut server, "evaluate", json!({ "expression": "input 21" }))[0]["body"]["result"], "1 value(s) added to the input");
+        assert_eq!(events(&request(&mut server, "continue", json!({ "threadId": 1 }))), vec!["stopped breakpoint"]);
+
+        let stack_trace = request(&mut server, "stackTrace", json!({ "threadId": 1 }));
+        assert_eq!(stack_trace[0]["body"]["stackFrames"][0]["line"], 3);
+        assert_eq!(stack_trace[0]["body"]["stackFrames"][0]["name"], "out [10]");
+
+        let memory = request(&mut server, "variables", json!({ "variablesReference": FIRST_RANGE_REFERENCE }));
+        assert_eq!(memory[0]["body"]["variables"][10]["value"], "42");
+
+        assert_eq!(events(&request(&mut server, "next", json!({ "threadId": 1 }))), vec!["output 42\n", "stopped step"]);
+        assert_eq!(events(&request(&mut server, "continue", json!({ "threadId": 1 }))), vec!["exited ", "terminated "]);
+
+        // Sequence numbers are increasing.
+        let seq = request(&mut server, "disconnect", json!({}))[0]["seq"].as_i64().unwrap();
+        assert_eq!(seq, 20);
+        assert!(server.is_disconnected());
+    }
+
+    #[test]
+    fn instruction_breakpoints_and_disassembly() {
+        let mut server = Server::new();
+        request(&mut server, "launch", json!({ "code": DOUBLE, "input": "5" }));
+        request(&mut server, "setInstructionBreakpoints", json!({ "breakpoints": [{ "instructionReference": "2" }] }));
+        assert_eq!(events(&request(&mut server, "configurationDone", json!({}))), vec!["stopped breakpoint"]);
+
+        let disassembly = request(&mut server, "disassemble", json!({ "memoryReference": "2", "instructionOffset": -1, "instructionCount": 3 }));
+        let instructions = &disassembly[0]["body"]["instructions"];
+        assert_eq!(instructions[0]["instruction"], "in [9]");
+        assert_eq!(instructions[2]["address"], "6");
+
+        let source = request(&mut server, "source", json!({ "sourceReference": 1 }));
+        assert!(source[0]["body"]["content"].as_str().unwrap().starts_with("     0  in [9]\n"));
+    }
+
+    #[test]
+    fn errors() {
+        let mut server = Server::new();
+        let response = &server.handle(&json!({ "seq": 1, "command": "stackTrace" }))[0];
+        assert_eq!(response["success"], false);
+        assert_eq!(response["message"], "No program launched");
+
+        request(&mut server, "launch", json!({ "code": [3,0,42] }));
+        request(&mut server, "evaluate", json!({ "expression": "input 1" }));
+        assert_eq!(events(&request(&mut server, "configurationDone", json!({}))), vec!["output Intcode error: UnknownOpCode { cursor: 2, op_code: 42 }\n", "terminated "]);
+        assert_eq!(server.handle(&json!({ "seq": 2, "command": "continue" }))[0]["success"], false);
+
+        // References given by the client which can't be represented.
+        let response = &server.handle(&json!({ "seq": 3, "command": "variables", "arguments": { "variablesReference": i64::MAX } }))[0];
+        assert_eq!(response["success"], false);
+        assert_eq!(response["message"], format!("Invalid variables reference: {}", i64::MAX));
+        let response = &server.handle(&json!({ "seq": 4, "command": "disassemble", "arguments": { "memoryReference": i64::MAX.to_string(), "offset": 1, "instructionCount": 1 } }))[0];
+        assert_eq!(response["success"], false);
+        let response = &server.handle(&json!({ "seq": 5, "command": "disassemble", "arguments": { "memoryReference": "0", "instructionOffset": i64::MAX, "instructionCount": 2 } }))[0];
+        assert_eq!(response["body"]["instructions"][1]["presentationHint"], "invalid");
+        request(&mut server, "setBreakpoints", json!({ "breakpoints": [{ "line": i64::MIN }] }));
+        request(&mut server, "setInstructionBreakpoints", json!({ "breakpoints": [{ "instructionReference": i64::MAX.to_string(), "offset": 1 }] }));
+    }
+
+    #[test]
+    fn relative_base_window() {
+        let mut server = Server::new();
+        request(&mut server, "launch", json!({ "code": [109,20,3,0,99], "stopOnEntry": true }));
+        request(&mut server, "configurationDone", json!({}));
+        request(&mut server, "next", json!({ "threadId": 1 }));
+
+        let cells = request(&mut server, "variables", json!({ "variablesReference": RELATIVE_BASE_REFERENCE }));
+        let names: Vec<&str> = cells[0]["body"]["variables"].as_array().unwrap().iter().map(|cell| cell["name"].as_str().unwrap()).collect();
+        assert_eq!(names.len(), 17);
+        assert_eq!((names[0], names[8], names[16]), ("[12]", "[20]", "[28]"));
+    }
+
+    #[test]
+    fn framing() {
+        let mut output = Vec::new();
+        write_message(&mut output, &json!({ "seq": 1, "command": "initialize" })).unwrap();
+        let mut input = output.clone();
+        write_message(&mut input, &json!({ "seq": 2, "command": "disconnect" })).unwrap();
+
+        let mut reader = io::Cursor::new(input);
+        assert_eq!(read_message(&mut reader).unwrap().unwrap()["command"], "initialize");
+
+        let mut reader = io::Cursor::new(output.iter().chain(b"Content-Length: 2\r\n\r\n{}").copied().collect::<Vec<u8>>());
+        let mut responses = Vec::new();
+        serve(&mut reader, &mut responses).unwrap();
+        let responses = String::from_utf8(responses).unwrap();
+        assert!(responses.starts_with("Content-Length: "));
+        assert!(responses.contains("\"event\":\"initialized\""));
+        assert!(responses.contains("Unsupported command"));
+    }
+}
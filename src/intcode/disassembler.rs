@@ -0,0 +1,78 @@
+use super::{Mode, optimizer::decode};
+
+// An instruction or a data cell of a disassembled program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line {
+    pub address: usize,
+    pub len: usize,
+    pub text: String,
+}
+
+fn mnemonic(op: i64) -> &'static str {
+    match op {
+        1 => "add",
+        2 => "mul",
+        3 => "in",
+        4 => "out",
+        5 => "jnz",
+        6 => "jz",
+        7 => "lt",
+        8 => "eq",
+        9 => "arb",
+        _ => "hlt"
+    }
+}
+
+fn operand(value: i64, mode: Mode) -> String {
+    match mode {
+        Mode::Position => format!("[{}]", value),
+        Mode::Immediate => value.to_string(),
+        Mode::Relative if value < 0 => format!("[rb-{}]", -value),
+        Mode::Relative => format!("[rb+{}]", value)
+    }
+}
+
+// Linear sweep: each valid instruction is decoded and any other value is shown as data.
+// The program may be disassembled wrongly if it modifies its instructions at runtime.
+pub fn disassemble(code: &[i64]) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut address = 0;
+    while address < code.len() {
+        let line =
+            match decode(code, address).filter(|instruction| address + instruction.len <= code.len()) {
+                Some(instruction) => {
+                    let operands: Vec<String> = (1 .. instruction.len).map(|i| operand(code[address + i], instruction.modes[i - 1])).collect();
+                    let text = if operands.is_empty() { String::from(mnemonic(instruction.op)) } else { format!("{} {}", mnemonic(instruction.op), operands.join(", ")) };
+                    Line { address, len: instruction.len, text }
+                },
+                None => Line { address, len: 1, text: format!("data {}", code[address]) }
+            };
+        address += line.len;
+        lines.push(line);
+    }
+    lines
+}
+
+// Index of the line containing the given address.
+pub fn line_of(lines: &[Line], address: usize) -> Option<usize> {
+    lines.iter().position(|line| address >= line.address && address < line.address + line.len)
+}
+
+pub fn to_string(lines: &[Line]) -> String {
+    lines.iter().map(|line| format!("{:>6}  {}\n", line.address, line.text)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disassemble_program() {
+        let lines = disassemble(&[3,9,1002,9,2,10,204,-1,99,0,1105]);
+        let texts: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(texts, vec!["in [9]", "mul [9], 2, [10]", "out [rb-1]", "hlt", "data 0", "data 1105"]);
+        assert_eq!(line_of(&lines, 4), Some(1));
+        assert_eq!(line_of(&lines, 11), None);
+        assert_eq!(to_string(&lines[.. 2]), "     0  in [9]\n     2  mul [9], 2, [10]\n");
+    }
+}
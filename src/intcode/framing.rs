@@ -0,0 +1,229 @@
+use alloc::vec::Vec;
+use core::{mem, num::NonZeroUsize};
+
+use super::{Context, IO, Input};
+
+// How the output values of a program are grouped into frames.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Framing {
+    Fixed(NonZeroUsize), // Frames of this number of values, an empty frame would never end.
+    Delimited(i64), // Frames terminated by this value, the delimiter isn't part of the frame.
+}
+
+impl Framing {
+    // 'Framing::Fixed' usable in a constant, panics if 'size' is 0.
+    pub const fn fixed(size: usize) -> Self {
+        match NonZeroUsize::new(size) {
+            Some(size) => Framing::Fixed(size),
+            None => panic!("A frame can't be empty")
+        }
+    }
+}
+
+// A type built from the values of one frame.
+pub trait Decode: Sized {
+    type Error;
+    fn decode(values: &[i64]) -> Result<Self, Self::Error>;
+}
+
+// Like 'IO' but receiving decoded frames instead of single values.
+pub trait Device {
+    type Frame: Decode;
+    fn read(&mut self) -> Input;
+    fn receive(&mut self, frame: Self::Frame);
+    fn halt(&self) -> bool { false }
+    fn finished(&mut self) { }
+
+    // See 'IO::read_with_context'.
+    fn read_with_context(&mut self, _context: &Context) -> Input { self.read() }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FrameError<E> {
+    Decode { index: usize, values: Vec<i64>, error: E }, // 'index' is the number of frames received before.
+    Incomplete { values: Vec<i64> }, // The program has halted in the middle of a frame.
+}
+
+// An 'IO' grouping the output values into frames and giving them decoded to a device.
+// The program is halted at the first frame that can't be decoded.
+pub struct Framed<D: Device> {
+    pub device: D,
+    framing: Framing,
+    buffer: Vec<i64>,
+    nb_frames: usize,
+    error: Option<FrameError<<D::Frame as Decode>::Error>>,
+}
+
+impl<D: Device> Framed<D> {
+    pub fn new(device: D, framing: Framing) -> Self {
+        Framed { device, framing, buffer: Vec::new(), nb_frames: 0, error: None }
+    }
+
+    pub fn nb_frames(&self) -> usize { self.nb_frames }
+
+    pub fn error(&self) -> Option<&FrameError<<D::Frame as Decode>::Error>> { self.error.as_ref() }
+
+    // Return the device if all the output has been decoded.
+    pub fn into_result(self) -> Result<D, FrameError<<D::Frame as Decode>::Error>> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.device)
+        }
+    }
+}
+
+impl<D: Device> IO for Framed<D> {
+    fn read(&mut self) -> Input {
+        self.device.read()
+    }
+
+    fn read_with_context(&mut self, context: &Context) -> Input {
+        self.device.read_with_context(context)
+    }
+
+    fn write(&mut self, value: i64) {
+        if self.error.is_some() { return }
+
+        let complete =
+            match self.framing {
+                Framing::Fixed(size) => {
+                    self.buffer.push(value);
+                    self.buffer.len() >= size.get()
+                },
+                Framing::Delimited(delimiter) if value == delimiter => true,
+                Framing::Delimited(_) => {
+                    self.buffer.push(value);
+                    false
+                }
+            };
+
+        if complete {
+            // The buffer is cleared rather than taken to keep its allocation for the next frame.
+            match D::Frame::decode(&self.buffer) {
+                Ok(frame) => {
+                    self.buffer.clear();
+                    self.nb_frames += 1;
+                    self.device.receive(frame)
+                },
+                Err(error) => self.error = Some(FrameError::Decode { index: self.nb_frames, values: mem::take(&mut self.buffer), error })
+            }
+        }
+    }
+
+    fn halt(&self) -> bool {
+        self.error.is_some() || self.device.halt()
+    }
+
+    fn finished(&mut self) {
+        if self.error.is_none() && !self.buffer.is_empty() {
+            self.error = Some(FrameError::Incomplete { values: mem::take(&mut self.buffer) });
+        }
+        self.device.finished()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Decoded<T> {
+    pub frames: Vec<T>,
+    pub incomplete: Vec<i64>, // Values of an incomplete last frame, empty if all frames are complete.
+}
+
+// Decode the whole output of a program, a decoding error stops at the first invalid frame.
+pub fn decode_all<T: Decode>(output: &[i64], framing: Framing) -> Result<Decoded<T>, FrameError<T::Error>> {
+    let frames: Vec<&[i64]> =
+        match framing {
+            Framing::Fixed(size) => output.chunks(size.get()).collect(),
+            Framing::Delimited(delimiter) => output.split(|value| *value == delimiter).collect()
+        };
+
+    let mut decoded = Decoded { frames: Vec::new(), incomplete: Vec::new() };
+    for (index, values) in frames.iter().enumerate() {
+        let is_last = index == frames.len() - 1;
+        let is_incomplete =
+            match framing {
+                Framing::Fixed(size) => values.len() < size.get(),
+                Framing::Delimited(_) => is_last // The last frame isn't terminated.
+            };
+        if is_last && is_incomplete {
+            decoded.incomplete = Vec::from(*values);
+            break
+        }
+        decoded.frames.push(T::decode(values).map_err(|error| FrameError::Decode { index, values: Vec::from(*values), error })?);
+    }
+    Ok(decoded)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use super::super::{Machine, Status};
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Pair(i64, i64);
+
+    impl Decode for Pair {
+        type Error = ();
+        fn decode(values: &[i64]) -> Result<Self, ()> {
+            match values {
+                [a, b] if *a >= 0 => Ok(Pair(*a, *b)),
+                _ => Err(())
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Line(String);
+
+    impl Decode for Line {
+        type Error = ();
+        fn decode(values: &[i64]) -> Result<Self, ()> {
+            Ok(Line(values.iter().map(|value| *value as u8 as char).collect()))
+        }
+    }
+
+    struct Pairs(Vec<Pair>);
+
+    impl Device for Pairs {
+        type Frame = Pair;
+        fn read(&mut self) -> Input { Input::Closed }
+        fn receive(&mut self, frame: Pair) { self.0.push(frame) }
+    }
+
+    fn run(code: &[i64]) -> Framed<Pairs> {
+        let mut framed = Framed::new(Pairs(Vec::new()), Framing::fixed(2));
+        assert_eq!(Machine::new(code).run(&mut framed), Ok(Status::Halted));
+        framed
+    }
+
+    #[test]
+    fn fixed_size_frames() {
+        let framed = run(&[104,1,104,2,104,3,104,4,99]);
+        assert_eq!(framed.nb_frames(), 2);
+        assert_eq!(framed.into_result().unwrap().0, vec![Pair(1, 2), Pair(3, 4)]);
+    }
+
+    #[test]
+    fn incomplete_frame_at_halt() {
+        let framed = run(&[104,1,104,2,104,3,99]);
+        assert_eq!(framed.error(), Some(&FrameError::Incomplete { values: vec![3] }));
+        assert_eq!(framed.device.0, vec![Pair(1, 2)]);
+    }
+
+    #[test]
+    fn decode_error_halts_the_program() {
+        // The third value would never be written.
+        let framed = run(&[104,-1,104,2,104,3,99]);
+        assert_eq!(framed.into_result().err(), Some(FrameError::Decode { index: 0, values: vec![-1, 2], error: () }));
+    }
+
+    #[test]
+    fn decode_all_output() {
+        assert_eq!(decode_all::<Pair>(&[1, 2, 3, 4, 5], Framing::fixed(2)), Ok(Decoded { frames: vec![Pair(1, 2), Pair(3, 4)], incomplete: vec![5] }));
+        assert_eq!(decode_all::<Pair>(&[1, 2, -3, 4], Framing::fixed(2)), Err(FrameError::Decode { index: 1, values: vec![-3, 4], error: () }));
+
+        let lines = decode_all::<Line>(&[72, 105, 10, 10, 33], Framing::Delimited(10)).unwrap();
+        assert_eq!(lines.frames, vec![Line(String::from("Hi")), Line(String::new())]);
+        assert_eq!(lines.incomplete, vec![33]);
+        assert_eq!(decode_all::<Line>(&[72, 10], Framing::Delimited(10)), Ok(Decoded { frames: vec![Line(String::from("H"))], incomplete: vec![] }));
+    }
+}
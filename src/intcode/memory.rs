@@ -0,0 +1,137 @@
+use alloc::{sync::Arc, vec::Vec};
+
+const PAGE_SIZE: usize = 128;
+
+type Page = [i64; PAGE_SIZE];
+
+// Memory of a machine. It's a flat vector until the machine is forked, from then it's split in pages shared between
+// the forks, a page is copied the first time it is written (copy-on-write).
+// Reading beyond the end returns 0 and writing beyond the end extends the memory.
+#[derive(Clone, Debug)]
+pub struct Memory {
+    storage: Storage,
+}
+
+#[derive(Clone, Debug)]
+enum Storage {
+    Flat(Vec<i64>),
+    Paged { pages: Vec<Arc<Page>>, len: usize },
+}
+
+impl Memory {
+    pub fn from(code: &[i64]) -> Self {
+        Memory { storage: Storage::Flat(Vec::from(code)) }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.storage {
+            Storage::Flat(cells) => cells.len(),
+            Storage::Paged { len, .. } => *len
+        }
+    }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    #[inline]
+    pub fn get(&self, address: usize) -> i64 {
+        match &self.storage {
+            Storage::Flat(cells) => cells.get(address).copied().unwrap_or(0),
+            Storage::Paged { pages, len } => if address >= *len { 0 } else { pages[address / PAGE_SIZE][address % PAGE_SIZE] }
+        }
+    }
+
+    #[inline]
+    pub fn set(&mut self, address: usize, value: i64) {
+        match &mut self.storage {
+            Storage::Flat(cells) => {
+                if address >= cells.len() {
+                    cells.resize(address + 1, 0);
+                }
+                cells[address] = value;
+            }
+            Storage::Paged { pages, len } => {
+                if address >= *len {
+                    pages.resize_with(address / PAGE_SIZE + 1, || Arc::new([0; PAGE_SIZE]));
+                    *len = address + 1;
+                }
+                Arc::make_mut(&mut pages[address / PAGE_SIZE])[address % PAGE_SIZE] = value;
+            }
+        }
+    }
+
+    // Split the memory in pages, a clone then shares them instead of copying all the cells.
+    pub fn make_paged(&mut self) {
+        if let Storage::Flat(cells) = &self.storage {
+            let pages =
+                cells.chunks(PAGE_SIZE).map(
+                    |chunk| {
+                        let mut page = [0; PAGE_SIZE];
+                        page[.. chunk.len()].copy_from_slice(chunk);
+                        Arc::new(page)
+                    }
+                ).collect();
+            self.storage = Storage::Paged { pages, len: cells.len() };
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<i64> {
+        match &self.storage {
+            Storage::Flat(cells) => cells.clone(),
+            Storage::Paged { pages, len } => pages.iter().flat_map(|page| page.iter().copied()).take(*len).collect()
+        }
+    }
+
+    // Zero while the memory is flat.
+    pub fn nb_pages(&self) -> usize {
+        match &self.storage {
+            Storage::Flat(_) => 0,
+            Storage::Paged { pages, .. } => pages.len()
+        }
+    }
+
+    // Number of pages physically shared with another memory.
+    pub fn nb_shared_pages(&self, other: &Memory) -> usize {
+        match (&self.storage, &other.storage) {
+            (Storage::Paged { pages: pages1, .. }, Storage::Paged { pages: pages2, .. }) =>
+                pages1.iter().zip(pages2.iter()).filter(|(p1, p2)| Arc::ptr_eq(p1, p2)).count(),
+            _ => 0
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_and_write() {
+        for paged in [false, true] {
+            let mut memory = Memory::from(&[1, 2, 3]);
+            if paged { memory.make_paged() }
+            assert_eq!(memory.get(2), 3);
+            assert_eq!(memory.get(3), 0);
+
+            memory.set(PAGE_SIZE + 1, 4);
+            assert_eq!(memory.len(), PAGE_SIZE + 2);
+            assert_eq!(memory.get(PAGE_SIZE + 1), 4);
+            assert_eq!(memory.to_vec()[.. 4], [1, 2, 3, 0]);
+            assert_eq!(memory.to_vec().len(), PAGE_SIZE + 2);
+            assert_eq!(memory.nb_pages(), if paged { 2 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn copy_on_write() {
+        let mut memory = Memory::from(&vec![7; 10 * PAGE_SIZE]);
+        assert_eq!(memory.clone().nb_shared_pages(&memory), 0);
+
+        memory.make_paged();
+        let mut fork = memory.clone();
+        assert_eq!(fork.nb_shared_pages(&memory), 10);
+
+        fork.set(3 * PAGE_SIZE, 8);
+        assert_eq!(fork.nb_shared_pages(&memory), 9);
+        assert_eq!(fork.get(3 * PAGE_SIZE), 8);
+        assert_eq!(memory.get(3 * PAGE_SIZE), 7);
+    }
+}
@@ -0,0 +1,369 @@
+use std::{collections::{BTreeMap, HashSet}, convert::TryFrom};
+
+use super::{Mode, read_op_and_modes};
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Options {
+    // Relative mode accesses are assumed to target addresses after the end of the program (the stack),
+    // otherwise nothing is optimized when the program uses them.
+    pub assume_stack_after_program: bool,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    pub nb_folded: usize, // Arithmetic, comparisons and conditions computed from constants.
+    pub nb_jumps_shortened: usize, // Jumps to a jump retargeted to the final destination.
+    pub nb_cells_removed: usize, // Unreachable code zeroed or truncated.
+    pub refused: bool, // The program can't be analysed, for example because it modifies its instructions at runtime.
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct Instruction {
+    pub op: i64,
+    pub modes: [Mode; 3],
+    pub len: usize,
+}
+
+pub(super) fn decode(code: &[i64], address: usize) -> Option<Instruction> {
+    let value = *code.get(address)?;
+    if value < 0 || (value / 100).to_string().chars().any(|digit| digit > '2') {
+        return None
+    }
+
+    let (op, modes) = read_op_and_modes(value);
+    let len =
+        match op {
+            1 | 2 | 7 | 8 => 4,
+            3 | 4 | 9 => 2,
+            5 | 6 => 3,
+            99 => 1,
+            _ => return None
+        };
+    Some(Instruction { op, modes, len })
+}
+
+pub(super) struct Analysis {
+    pub instructions: BTreeMap<usize, Instruction>, // Reachable instructions by address.
+    pub written: HashSet<usize>, // Addresses written at runtime.
+    pub read: HashSet<usize>, // Addresses read as data.
+    pub unknown_jumps: bool, // Some jump targets can't be determined statically.
+    pub unknown_reads: bool, // Some read addresses can't be determined statically.
+}
+
+impl Analysis {
+    pub fn is_written(&self, address: usize, len: usize) -> bool {
+        (address .. address + len).any(|a| self.written.contains(&a))
+    }
+
+    // The cells may be read as data by another instruction.
+    pub fn may_be_read(&self, address: usize, len: usize) -> bool {
+        self.unknown_reads || (address .. address + len).any(|a| self.read.contains(&a))
+    }
+
+    // Value of a parameter if it can't change at runtime.
+    pub fn constant(&self, code: &[i64], position: usize, mode: Mode) -> Option<i64> {
+        if self.written.contains(&position) { return None }
+        match mode {
+            Mode::Immediate => Some(code.get(position).copied().unwrap_or(0)),
+            Mode::Position => {
+                let address = usize::try_from(*code.get(position)?).ok()?;
+                if address < code.len() && !self.written.contains(&address) { Some(code[address]) } else { None }
+            },
+            Mode::Relative => None
+        }
+    }
+
+    pub fn condition(&self, code: &[i64], address: usize, instruction: &Instruction) -> Option<bool> {
+        self.constant(code, address + 1, instruction.modes[0]).map(|value| (value != 0) == (instruction.op == 5))
+    }
+
+    fn is_unconditional_jump(&self, code: &[i64], address: usize) -> Option<usize> {
+        let instruction = self.instructions.get(&address)?;
+        if (instruction.op == 5 || instruction.op == 6) && !self.is_written(address, instruction.len) && self.condition(code, address, instruction) == Some(true) {
+            usize::try_from(self.constant(code, address + 2, instruction.modes[1])?).ok()
+        } else {
+            None
+        }
+    }
+}
+
+fn analyse(code: &[i64], options: Options) -> Option<Analysis> {
+    analyse_from(code, options, &[0])
+}
+
+// Explore all the instructions reachable from the entries, return 'None' if the program can't be analysed.
+pub(super) fn analyse_from(code: &[i64], options: Options, entries: &[usize]) -> Option<Analysis> {
+    let mut analysis = Analysis { instructions: BTreeMap::new(), written: HashSet::new(), read: HashSet::new(), unknown_jumps: false, unknown_reads: false };
+
+    // The written addresses change the reachable instructions and vice versa: iterate until nothing changes.
+    loop {
+        let mut written = HashSet::<usize>::new();
+        analysis.instructions.clear();
+        analysis.read.clear();
+        analysis.unknown_jumps = false;
+        analysis.unknown_reads = false;
+
+        let mut to_visit = Vec::from(entries);
+        while let Some(address) = to_visit.pop() {
+            if analysis.instructions.contains_key(&address) { continue }
+            if analysis.written.contains(&address) { return None } // The instruction itself may change.
+
+            // An invalid instruction stops the program.
+            let instruction = match decode(code, address) { Some(instruction) => instruction, None => continue };
+
+            let (reads, write): (&[usize], Option<usize>) =
+                match instruction.op {
+                    1 | 2 | 7 | 8 => (&[0, 1], Some(2)),
+                    3 => (&[], Some(0)),
+                    4 | 9 => (&[0], None),
+                    5 | 6 => (&[0, 1], None),
+                    _ => (&[], None)
+                };
+
+            for &p in reads {
+                match instruction.modes[p] {
+                    Mode::Immediate => (),
+                    Mode::Position if !analysis.written.contains(&(address + 1 + p)) => { analysis.read.insert(code.get(address + 1 + p).copied().unwrap_or(0) as usize); },
+                    Mode::Relative if options.assume_stack_after_program => (),
+                    _ => analysis.unknown_reads = true
+                }
+            }
+
+            if let Some(p) = write {
+                match instruction.modes[p] {
+                    Mode::Position if !analysis.written.contains(&(address + 1 + p)) => { written.insert(code.get(address + 1 + p).copied().unwrap_or(0) as usize); },
+                    Mode::Relative if options.assume_stack_after_program => (),
+                    Mode::Immediate => { analysis.instructions.insert(address, instruction); continue }, // Stops the program.
+                    _ => return None // Unknown destination.
+                }
+            }
+
+            match instruction.op {
+                99 => (),
+                5 | 6 => {
+                    let condition = analysis.condition(code, address, &instruction);
+                    if condition != Some(true) {
+                        to_visit.push(address + instruction.len);
+                    }
+                    if condition != Some(false) {
+                        match analysis.constant(code, address + 2, instruction.modes[1]).map(usize::try_from) {
+                            Some(Ok(target)) => to_visit.push(target),
+                            Some(Err(_)) => (),
+                            None => analysis.unknown_jumps = true
+                        }
+                    }
+                },
+                _ => to_visit.push(address + instruction.len)
+            }
+
+            analysis.instructions.insert(address, instruction);
+        }
+
+        if written.is_subset(&analysis.written) {
+            return Some(analysis)
+        }
+        analysis.written.extend(written);
+    }
+}
+
+// Rewrite the program into an equivalent faster one, the regions written or read as data at runtime are never touched.
+pub fn optimize(code: &[i64], options: Options) -> (Vec<i64>, Report) {
+    let mut report = Report::default();
+    let mut optimized = Vec::from(code);
+
+    let analysis =
+        match analyse(code, options) {
+            Some(analysis) => analysis,
+            None => {
+                report.refused = true;
+                return (optimized, report)
+            }
+        };
+
+    for (&address, instruction) in analysis.instructions.iter() {
+        if analysis.is_written(address, instruction.len) || analysis.may_be_read(address, instruction.len) { continue }
+
+        let output_mode = match instruction.modes[2] { Mode::Relative => 2, _ => 0 };
+
+        match instruction.op {
+            // Replace by a sum of the result and 0.
+            1 | 2 | 7 | 8 => {
+                let a = analysis.constant(code, address + 1, instruction.modes[0]);
+                let b = analysis.constant(code, address + 2, instruction.modes[1]);
+                // An overflow is left to the execution.
+                let result =
+                    match (a, b, instruction.op) {
+                        (Some(a), Some(b), 1) => a.checked_add(b),
+                        (Some(a), Some(b), 2) => a.checked_mul(b),
+                        (Some(a), Some(b), 7) => Some(if a < b { 1 } else { 0 }),
+                        (Some(a), Some(b), _) => Some(if a == b { 1 } else { 0 }),
+                        _ => None
+                    };
+                if let Some(result) = result {
+                    let folded = [1101 + output_mode * 10_000, result, 0];
+                    if optimized[address .. address + 3] != folded {
+                        optimized[address .. address + 3].copy_from_slice(&folded);
+                        report.nb_folded += 1;
+                    }
+                }
+            },
+
+            5 | 6 => {
+                // Replace by an unconditional jump.
+                if analysis.condition(code, address, instruction) == Some(true) && instruction.modes[0] != Mode::Immediate {
+                    optimized[address] = 105 + 1000 * match instruction.modes[1] { Mode::Position => 0, Mode::Immediate => 1, Mode::Relative => 2 };
+                    optimized[address + 1] = 1;
+                    report.nb_folded += 1;
+                }
+
+                // Follow the jumps to jumps.
+                if let Some(mut target) = analysis.constant(code, address + 2, instruction.modes[1]).and_then(|t| usize::try_from(t).ok()) {
+                    let mut visited = HashSet::from([address]);
+                    let mut shortened = false;
+                    while let Some(next_target) = analysis.is_unconditional_jump(code, target) {
+                        if !visited.insert(target) { break } // Infinite loop.
+                        target = next_target;
+                        shortened = true;
+                    }
+                    if shortened {
+                        optimized[address] = optimized[address] % 1000 + 1000;
+                        optimized[address + 2] = target as i64;
+                        report.nb_jumps_shortened += 1;
+                    }
+                }
+            },
+
+            _ => ()
+        }
+    }
+
+    // Some jumps may be unreachable now.
+    let analysis = match analyse(&optimized, options) { Some(analysis) => analysis, None => return (Vec::from(code), Report { refused: true, ..Report::default() }) };
+
+    if !analysis.unknown_jumps && !analysis.unknown_reads {
+        let mut reachable = HashSet::new();
+        for (&address, instruction) in analysis.instructions.iter() {
+            reachable.extend(address .. address + instruction.len);
+        }
+
+        for (address, value) in optimized.iter_mut().enumerate() {
+            if *value != 0 && !reachable.contains(&address) && !analysis.read.contains(&address) && !analysis.written.contains(&address) {
+                *value = 0;
+                report.nb_cells_removed += 1;
+            }
+        }
+    }
+
+    // Reading beyond the end of the memory returns 0.
+    while optimized.last() == Some(&0) {
+        optimized.pop();
+    }
+
+    (optimized, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{execute_op_code, record};
+    use crate::{common, day11, day13, day15};
+
+    #[test]
+    fn fold_constants() {
+        // Write 3 + 4 in [11] and output [13] * 7 where [13] is never written.
+        let c = [1101,3,4,11,1002,13,7,12,4,12,99,0,0,6];
+        let (optimized, report) = optimize(&c, Options::default());
+        assert_eq!(report, Report { nb_folded: 2, nb_cells_removed: 1, ..Report::default() });
+        assert_eq!(optimized, [1101,7,0,11,1101,42,0,12,4,12,99]);
+        assert_eq!(execute_op_code(&optimized, &[]), vec![42]);
+
+        // The overflowing sum and product are kept.
+        let c = [1101,i64::MAX,1,13,1102,i64::MAX,2,13,1101,2,3,13,99,0];
+        let (optimized, report) = optimize(&c, Options::default());
+        assert_eq!(report.nb_folded, 1);
+        assert_eq!(optimized[.. 8], c[.. 8]);
+    }
+
+    #[test]
+    fn shorten_jump_chains_and_remove_unreachable_code() {
+        // Jump to 6 which jumps to 9 which outputs the input.
+        let c = [1105,1,6,4,1,99,1106,0,9,3,15,4,15,99,1,0];
+        let (optimized, report) = optimize(&c, Options::default());
+        assert_eq!(report, Report { nb_jumps_shortened: 1, nb_cells_removed: 6, ..Report::default() });
+        assert_eq!(optimized, [1105,1,9,0,0,0,0,0,0,3,15,4,15,99]);
+        assert_eq!(execute_op_code(&optimized, &[7]), vec![7]);
+    }
+
+    #[test]
+    fn do_not_touch_written_instructions() {
+        // The first instruction changes the second operand of the second one, then [11] is output.
+        let c = [1101,0,7,6,1101,1,0,11,4,11,99,0];
+        let (optimized, report) = optimize(&c, Options::default());
+        assert_eq!(report, Report { nb_folded: 1, ..Report::default() });
+        assert_eq!(optimized, [1101,7,0,6,1101,1,0,11,4,11,99]);
+        assert_eq!(execute_op_code(&optimized, &[]), vec![8]);
+    }
+
+    #[test]
+    fn do_not_touch_instructions_read_as_data() {
+        // Output the second operand of the sum, then the sum.
+        let c = [1101,3,4,10,4,2,4,10,99,0,0];
+        let (optimized, report) = optimize(&c, Options::default());
+        assert_eq!(report, Report::default());
+        assert_eq!(execute_op_code(&optimized, &[]), vec![4, 7]);
+
+        // Jump to 5 which jumps to 9 which outputs the target of the first jump.
+        let c = [1105,1,5,0,0,1105,1,9,0,4,2,99];
+        let (optimized, report) = optimize(&c, Options::default());
+        assert_eq!(report, Report::default());
+        assert_eq!(execute_op_code(&optimized, &[]), vec![5]);
+
+        // Output the first operand of the sum through a relative read which can't be resolved statically.
+        let c = [1101,3,4,9,204,1,4,9,99,0];
+        let (optimized, report) = optimize(&c, Options::default());
+        assert_eq!(report, Report::default());
+        assert_eq!(execute_op_code(&optimized, &[]), vec![3, 7]);
+    }
+
+    #[test]
+    fn refuse_self_modifying_code() {
+        // The first instruction replaces the second one by a halt.
+        let c = [1101,0,99,4,1101,1,1,0,99];
+        assert_eq!(optimize(&c, Options::default()), (Vec::from(&c[..]), Report { refused: true, ..Report::default() }));
+
+        // Relative mode is only allowed with the stack assumption.
+        let c = [109,10,21101,1,2,0,204,0,99];
+        assert_eq!(optimize(&c, Options::default()).1, Report { refused: true, ..Report::default() });
+        let (optimized, report) = optimize(&c, Options { assume_stack_after_program: true });
+        assert_eq!(report, Report { nb_folded: 1, ..Report::default() });
+        assert_eq!(execute_op_code(&optimized, &[]), vec![3]);
+    }
+
+    fn check_equivalence(code: &[i64], session: &record::Session) -> Report {
+        let (optimized, report) = optimize(code, Options { assume_stack_after_program: true });
+        assert_eq!(record::replay_values(&optimized, session, &mut |_| ()), Ok(super::super::Status::Halted));
+        report
+    }
+
+    #[test]
+    fn equivalence_on_puzzles() {
+        // Only day 9 and day 11 are transformed, day 5 modifies its instructions and days 13 and 15 have nothing to fold.
+        let folded = |nb_folded| Report { nb_folded, ..Report::default() };
+        let refused = Report { refused: true, ..Report::default() };
+        for (file, inputs, expected) in [("data/day05.input", vec![1], refused), ("data/day05.input", vec![5], refused), ("data/day09.input", vec![1], folded(25))] {
+            let code = common::read_list_of_numbers::<&str, i64>(file, ",");
+            let mut session = record::Session::new();
+            record::record(&mut super::super::Machine::new(&code), &mut super::super::Buffer::from(&inputs), &mut session).unwrap();
+            assert_eq!(check_equivalence(&code, &session), expected);
+        }
+
+        let code = common::read_list_of_numbers::<&str, i64>("data/day11.input", ",");
+        assert_eq!(check_equivalence(&code, &day11::record_painting(&code, day11::Robot::default())), folded(10));
+
+        let code = day13::insert_quarters().apply(&common::read_list_of_numbers::<&str, i64>("data/day13.input", ","));
+        assert_eq!(check_equivalence(&code, &day13::record_game(&code).1), Report::default());
+
+        let code = common::read_list_of_numbers::<&str, i64>("data/day15.input", ",");
+        assert_eq!(check_equivalence(&code, &day15::record_exploration(&code)), Report::default());
+    }
+}
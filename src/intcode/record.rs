@@ -0,0 +1,331 @@
+use std::{fmt, fs, io, path::Path};
+
+use super::{Context, Error, IO, Input, Machine, Status};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    Read { instruction: u64, value: i64 }, // Value supplied by the IO.
+    Write { instruction: u64, value: i64 }, // Value received by the IO.
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Read { instruction, value } => write!(f, "read {} {}", instruction, value),
+            Event::Write { instruction, value } => write!(f, "write {} {}", instruction, value)
+        }
+    }
+}
+
+// All the values exchanged between a program and its IO, in order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Session {
+    pub events: Vec<Event>,
+    pub stopped_by_io: bool, // The IO has stopped the program (see 'IO::halt') after the last event.
+}
+
+impl Session {
+    pub fn new() -> Self { Session { events: Vec::new(), stopped_by_io: false } }
+
+    // One event per line: "read <instruction count> <value>" or "write <instruction count> <value>",
+    // the last line is "stop" if the IO has stopped the program.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut session = Session::new();
+        for (n, line) in input.lines().map(str::trim).enumerate().filter(|(_, line)| !line.is_empty()) {
+            if session.stopped_by_io {
+                return Err(format!("Line {}: event after stop", n + 1))
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let event =
+                match fields[..] {
+                    ["stop"] => {
+                        session.stopped_by_io = true;
+                        continue
+                    },
+                    [kind, instruction, value] => {
+                        let instruction = instruction.parse::<u64>().map_err(|error| format!("Line {}: {}", n + 1, error))?;
+                        let value = value.parse::<i64>().map_err(|error| format!("Line {}: {}", n + 1, error))?;
+                        match kind {
+                            "read" => Event::Read { instruction, value },
+                            "write" => Event::Write { instruction, value },
+                            _ => return Err(format!("Line {}: unknown event \"{}\"", n + 1, kind))
+                        }
+                    },
+                    _ => return Err(format!("Line {}: malformed event \"{}\"", n + 1, line))
+                };
+            session.events.push(event);
+        }
+        Ok(session)
+    }
+
+    pub fn load<P: AsRef<Path>>(file: P) -> io::Result<Self> {
+        Session::parse(&fs::read_to_string(file)?).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, file: P) -> io::Result<()> {
+        fs::write(file, self.to_string())
+    }
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for event in self.events.iter() {
+            writeln!(f, "{}", event)?;
+        }
+        if self.stopped_by_io {
+            writeln!(f, "stop")?;
+        }
+        Ok(())
+    }
+}
+
+struct Recorder<'a> {
+    io: &'a mut dyn IO,
+    instruction: u64,
+    session: &'a mut Session,
+}
+
+impl Recorder<'_> {
+    fn record_read(&mut self, input: Input) -> Input {
+        if let Input::Value(value) = input {
+            self.session.events.push(Event::Read { instruction: self.instruction, value });
+        }
+        input
+    }
+
+    fn record_write(&mut self, value: i64) {
+        self.session.events.push(Event::Write { instruction: self.instruction, value });
+    }
+}
+
+impl IO for Recorder<'_> {
+    fn read(&mut self) -> Input {
+        let input = self.io.read();
+        self.record_read(input)
+    }
+
+    fn write(&mut self, value: i64) {
+        self.record_write(value);
+        self.io.write(value)
+    }
+
+    fn halt(&self) -> bool { self.io.halt() }
+
+    fn finished(&mut self) { self.io.finished() }
+
+    fn read_with_context(&mut self, context: &Context) -> Input {
+        let input = self.io.read_with_context(context);
+        self.record_read(input)
+    }
+
+    fn write_with_context(&mut self, value: i64, context: &Context) {
+        self.record_write(value);
+        self.io.write_with_context(value, context)
+    }
+}
+
+// Run the machine like 'Machine::run' and append all the exchanged values to the session.
+pub fn record(machine: &mut Machine, io: &mut dyn IO, session: &mut Session) -> Result<Status, Error> {
+    let mut recorder = Recorder { io, instruction: 0, session };
+    loop {
+        recorder.instruction = machine.instruction_count();
+        if let Some(status) = machine.step(&mut recorder)? {
+            if status == Status::Halted && recorder.io.halt() {
+                recorder.session.stopped_by_io = true;
+            }
+            return Ok(status)
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReplayError {
+    Intcode(Error),
+    // The program doesn't behave as recorded. 'expected' is the recorded event at 'index' ('None' if the session is over)
+    // and 'actual' what the program did ('None' if it halted). The value of an unexpected read is unknown and set to 0.
+    Divergence { index: usize, expected: Option<Event>, actual: Option<Event> },
+}
+
+struct Player<'a> {
+    events: &'a [Event],
+    stopped_by_io: bool,
+    position: usize,
+    instruction: u64,
+    check_instructions: bool,
+    divergence: Option<ReplayError>,
+    on_event: &'a mut dyn FnMut(&Event),
+}
+
+impl Player<'_> {
+    fn next(&mut self, actual: Event) -> Option<Event> {
+        let expected = self.events.get(self.position).copied();
+        let matches =
+            match (expected, actual) {
+                (Some(Event::Read { instruction, .. }), Event::Read { .. }) => !self.check_instructions || instruction == self.instruction,
+                (Some(Event::Write { value: expected_value, .. }), Event::Write { value, .. }) if !self.check_instructions => expected_value == value,
+                (Some(expected), actual) => expected == actual,
+                (None, _) => false
+            };
+
+        if matches {
+            self.position += 1;
+            (self.on_event)(&expected.unwrap());
+            expected
+        } else {
+            self.divergence = Some(ReplayError::Divergence { index: self.position, expected, actual: Some(actual) });
+            None
+        }
+    }
+}
+
+impl IO for Player<'_> {
+    fn read(&mut self) -> Input {
+        match self.next(Event::Read { instruction: self.instruction, value: 0 }) {
+            Some(Event::Read { value, .. }) => Input::Value(value),
+            _ => Input::Closed
+        }
+    }
+
+    fn write(&mut self, value: i64) {
+        self.next(Event::Write { instruction: self.instruction, value });
+    }
+
+    fn halt(&self) -> bool {
+        self.divergence.is_some() || self.stopped_by_io && self.position == self.events.len()
+    }
+}
+
+// Run the program with the values of a recorded session instead of its original IO. 'on_event' is called
+// for each replayed event, for example to display the state or to slow down the replay.
+pub fn replay(code: &[i64], session: &Session, on_event: &mut dyn FnMut(&Event)) -> Result<Status, ReplayError> {
+    play(code, session, true, on_event)
+}
+
+// Same as 'replay' but only the exchanged values are checked, not the instruction counts. Used to check
+// that a different program (for example an optimized one) is equivalent to the recorded one.
+pub fn replay_values(code: &[i64], session: &Session, on_event: &mut dyn FnMut(&Event)) -> Result<Status, ReplayError> {
+    play(code, session, false, on_event)
+}
+
+fn play(code: &[i64], session: &Session, check_instructions: bool, on_event: &mut dyn FnMut(&Event)) -> Result<Status, ReplayError> {
+    let mut machine = Machine::new(code);
+    let mut player = Player { events: &session.events, stopped_by_io: session.stopped_by_io, position: 0, instruction: 0, check_instructions, divergence: None, on_event };
+
+    let result =
+        loop {
+            player.instruction = machine.instruction_count();
+            match machine.step(&mut player) {
+                Ok(None) => (),
+                Ok(Some(status)) => break Ok(status),
+                Err(error) => break Err(error)
+            }
+        };
+
+    if let Some(divergence) = player.divergence {
+        return Err(divergence)
+    }
+
+    let status = result.map_err(ReplayError::Intcode)?;
+
+    if player.position < session.events.len() {
+        return Err(ReplayError::Divergence { index: player.position, expected: Some(session.events[player.position]), actual: None })
+    }
+
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Buffer;
+
+    // Output twice the input until the input is 0.
+    const DOUBLE: [i64; 15] = [3,13,1002,13,2,14,4,14,1005,13,0,99,0,0,0];
+
+    fn recorded_session() -> Session {
+        let mut session = Session::new();
+        let mut buffer = Buffer::from(&[3, 5, 0]);
+        assert_eq!(record(&mut Machine::new(&DOUBLE), &mut buffer, &mut session), Ok(Status::Halted));
+        assert_eq!(buffer.output, vec![6, 10, 0]);
+        session
+    }
+
+    #[test]
+    fn record_events() {
+        let session = recorded_session();
+        assert_eq!(session.events, vec![
+            Event::Read { instruction: 0, value: 3 }, Event::Write { instruction: 2, value: 6 },
+            Event::Read { instruction: 4, value: 5 }, Event::Write { instruction: 6, value: 10 },
+            Event::Read { instruction: 8, value: 0 }, Event::Write { instruction: 10, value: 0 }
+        ]);
+    }
+
+    // Stop the program after 'n' outputs.
+    struct Stopper { output: Vec<i64>, n: usize }
+
+    impl IO for Stopper {
+        fn read(&mut self) -> Input { Input::Value(1) }
+        fn write(&mut self, value: i64) { self.output.push(value) }
+        fn halt(&self) -> bool { self.output.len() >= self.n }
+    }
+
+    #[test]
+    fn stopped_by_io() {
+        let mut session = Session::new();
+        let mut stopper = Stopper { output: Vec::new(), n: 2 };
+        assert_eq!(record(&mut Machine::new(&DOUBLE), &mut stopper, &mut session), Ok(Status::Halted));
+        assert!(session.stopped_by_io);
+        assert_eq!(session.events.len(), 4);
+        assert_eq!(Session::parse(&session.to_string()), Ok(session.clone()));
+        assert_eq!(replay(&DOUBLE, &session, &mut |_| ()), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let session = recorded_session();
+        let file = std::env::temp_dir().join(format!("intcode_session_{}.log", std::process::id()));
+        session.save(&file).unwrap();
+        let loaded = Session::load(&file);
+        fs::remove_file(&file).unwrap();
+        assert_eq!(loaded.unwrap(), session);
+
+        assert!(Session::parse("read 1 2\njump 3 4").is_err());
+    }
+
+    #[test]
+    fn replay_session() {
+        let session = recorded_session();
+        let mut replayed = Vec::new();
+        assert_eq!(replay(&DOUBLE, &session, &mut |event| replayed.push(*event)), Ok(Status::Halted));
+        assert_eq!(replayed, session.events);
+    }
+
+    #[test]
+    fn replay_divergence() {
+        let session = recorded_session();
+
+        // Output three times the input.
+        let mut triple = DOUBLE;
+        triple[4] = 3;
+        assert_eq!(
+            replay(&triple, &session, &mut |_| ()),
+            Err(ReplayError::Divergence { index: 1, expected: Some(Event::Write { instruction: 2, value: 6 }), actual: Some(Event::Write { instruction: 2, value: 9 }) })
+        );
+
+        // Same values but more instructions.
+        let mut slower = DOUBLE.to_vec();
+        slower[10] = 15;
+        slower.extend_from_slice(&[1105, 1, 0]);
+        assert!(matches!(replay(&slower, &session, &mut |_| ()), Err(ReplayError::Divergence { index: 2, .. })));
+        assert_eq!(replay_values(&slower, &session, &mut |_| ()), Ok(Status::Halted));
+
+        // Stop after the first output.
+        let mut once = DOUBLE;
+        once[8] = 99;
+        assert_eq!(
+            replay(&once, &session, &mut |_| ()),
+            Err(ReplayError::Divergence { index: 2, expected: Some(Event::Read { instruction: 4, value: 5 }), actual: None })
+        );
+    }
+}
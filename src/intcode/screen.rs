@@ -0,0 +1,264 @@
+// A screen for arcade programs like the one of day 13. The program draws with triplets (x, y, tile id),
+// the triplet (-1, 0, score) sets the score display.
+
+use std::{convert::TryFrom, mem};
+
+use num_enum::TryFromPrimitive;
+
+use super::{Input, framing::{Decode, Device, Framing}};
+
+pub const FRAMING: Framing = Framing::fixed(3);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, TryFromPrimitive)]
+#[repr(u8)]
+pub enum Tile {
+    Empty,
+    Wall,
+    Block,
+    Paddle,
+    Ball,
+}
+
+impl Tile {
+    pub fn glyph(self) -> char {
+        match self {
+            Tile::Empty => ' ',
+            Tile::Wall => '█',
+            Tile::Block => '▒',
+            Tile::Paddle => '▬',
+            Tile::Ball => '●'
+        }
+    }
+}
+
+// A frame of the output: a tile drawn at a position or the score.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Segment {
+    Tile { x: usize, y: usize, tile: Tile },
+    Score(i64),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SegmentError {
+    UnknownTile(i64),
+    InvalidPosition { x: i64, y: i64 },
+    InvalidLength(usize), // A segment has exactly 3 values.
+}
+
+impl Decode for Segment {
+    type Error = SegmentError;
+
+    fn decode(values: &[i64]) -> Result<Self, SegmentError> {
+        match *values {
+            [-1, 0, score] => Ok(Segment::Score(score)),
+            [x, y, id] => {
+                let tile = u8::try_from(id).ok().and_then(|id| Tile::try_from(id).ok()).ok_or(SegmentError::UnknownTile(id))?;
+                match (usize::try_from(x), usize::try_from(y)) {
+                    (Ok(x), Ok(y)) => Ok(Segment::Tile { x, y, tile }),
+                    _ => Err(SegmentError::InvalidPosition { x, y })
+                }
+            },
+            _ => Err(SegmentError::InvalidLength(values.len()))
+        }
+    }
+}
+
+// What has changed on the screen between two inputs of the program.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Frame {
+    pub number: usize,
+    pub changed: Vec<(usize, usize)>, // Positions of the cells with a different tile, in drawing order.
+    pub score_changed: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Screen {
+    rows: Vec<Vec<Tile>>, // All rows have the same width.
+    score: Option<i64>,
+    ball: Option<(usize, usize)>, // Where the ball and the paddle were last drawn, to follow them without looking for them.
+    paddle: Option<(usize, usize)>,
+    current: Frame,
+}
+
+impl Screen {
+    pub fn new() -> Self { Screen::default() }
+
+    pub fn width(&self) -> usize { self.rows.first().map_or(0, Vec::len) }
+
+    pub fn height(&self) -> usize { self.rows.len() }
+
+    pub fn rows(&self) -> &[Vec<Tile>] { &self.rows }
+
+    pub fn get(&self, x: usize, y: usize) -> Tile {
+        self.rows.get(y).and_then(|row| row.get(x)).copied().unwrap_or(Tile::Empty)
+    }
+
+    pub fn score(&self) -> Option<i64> { self.score }
+
+    pub fn ball(&self) -> Option<(usize, usize)> { self.ball }
+
+    pub fn paddle(&self) -> Option<(usize, usize)> { self.paddle }
+
+    // Position of the first given tile, row by row.
+    pub fn find(&self, tile: Tile) -> Option<(usize, usize)> {
+        self.rows.iter().enumerate().find_map(|(y, row)| row.iter().position(|t| *t == tile).map(|x| (x, y)))
+    }
+
+    pub fn count(&self, tile: Tile) -> usize {
+        self.rows.iter().map(|row| row.iter().filter(|t| **t == tile).count()).sum()
+    }
+
+    pub fn draw(&mut self, segment: Segment) {
+        match segment {
+            Segment::Score(score) => {
+                self.current.score_changed |= self.score != Some(score);
+                self.score = Some(score);
+            },
+            Segment::Tile { x, y, tile } => {
+                match tile {
+                    Tile::Ball => self.ball = Some((x, y)),
+                    Tile::Paddle => self.paddle = Some((x, y)),
+                    _ => ()
+                }
+                if x >= self.width() {
+                    for row in self.rows.iter_mut() {
+                        row.resize(x + 1, Tile::Empty);
+                    }
+                }
+                if y >= self.height() {
+                    let width = self.width().max(x + 1);
+                    self.rows.resize(y + 1, vec![Tile::Empty; width]);
+                }
+                if self.rows[y][x] != tile {
+                    self.rows[y][x] = tile;
+                    self.current.changed.push((x, y));
+                }
+            }
+        }
+    }
+
+    // Return the changes since the previous frame and start a new one.
+    pub fn end_frame(&mut self) -> Frame {
+        let number = self.current.number;
+        mem::replace(&mut self.current, Frame { number: number + 1, ..Frame::default() })
+    }
+
+    // The whole screen, one line per row, followed by the score if any.
+    pub fn render(&self) -> String {
+        let mut lines: Vec<String> = self.rows.iter().map(|row| row.iter().map(|tile| tile.glyph()).collect()).collect();
+        if let Some(score) = self.score {
+            lines.push(format!("Score: {}", score));
+        }
+        lines.join("\n")
+    }
+
+    // ANSI escape sequences redrawing only the changed cells of a screen rendered with 'render'
+    // at the top left of the terminal.
+    pub fn render_changes(&self, frame: &Frame) -> String {
+        let mut result: String = frame.changed.iter().map(|(x, y)| format!("\x1b[{};{}H{}", y + 1, x + 1, self.get(*x, *y).glyph())).collect();
+        if let (true, Some(score)) = (frame.score_changed, self.score) {
+            result += &format!("\x1b[{};1HScore: {}\x1b[K", self.height() + 1, score);
+        }
+        result
+    }
+}
+
+// Supplies the inputs of an arcade program, typically the joystick position.
+pub trait Controller {
+    // Called each time the program reads an input, the frame is then complete.
+    fn input(&mut self, screen: &Screen, frame: &Frame) -> Input;
+
+    // Stop the program before its next instruction, like a player leaving the game.
+    fn halt(&self) -> bool { false }
+
+    // Called when the program halts with the last frame.
+    fn finished(&mut self, _screen: &Screen, _frame: &Frame) { }
+}
+
+impl<F: FnMut(&Screen, &Frame) -> Input> Controller for F {
+    fn input(&mut self, screen: &Screen, frame: &Frame) -> Input { self(screen, frame) }
+}
+
+// A device made of a screen and a controller, to be used with 'framing::Framed' and 'FRAMING'.
+pub struct Arcade<C: Controller> {
+    pub screen: Screen,
+    pub controller: C,
+}
+
+impl<C: Controller> Arcade<C> {
+    pub fn new(controller: C) -> Self {
+        Arcade { screen: Screen::new(), controller }
+    }
+}
+
+impl<C: Controller> Device for Arcade<C> {
+    type Frame = Segment;
+
+    fn read(&mut self) -> Input {
+        let frame = self.screen.end_frame();
+        self.controller.input(&self.screen, &frame)
+    }
+
+    fn receive(&mut self, segment: Segment) {
+        self.screen.draw(segment)
+    }
+
+    fn halt(&self) -> bool { self.controller.halt() }
+
+    fn finished(&mut self) {
+        let frame = self.screen.end_frame();
+        self.controller.finished(&self.screen, &frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Machine, Status, framing::Framed};
+
+    #[test]
+    fn decode_segments() {
+        assert_eq!(Segment::decode(&[3, 2, 4]), Ok(Segment::Tile { x: 3, y: 2, tile: Tile::Ball }));
+        assert_eq!(Segment::decode(&[-1, 0, 42]), Ok(Segment::Score(42)));
+        assert_eq!(Segment::decode(&[1, 1, 7]), Err(SegmentError::UnknownTile(7)));
+        assert_eq!(Segment::decode(&[-2, 1, 0]), Err(SegmentError::InvalidPosition { x: -2, y: 1 }));
+        assert_eq!(Segment::decode(&[1, 1]), Err(SegmentError::InvalidLength(2)));
+    }
+
+    #[test]
+    fn draw_and_render() {
+        let mut screen = Screen::new();
+        for segment in [Segment::Tile { x: 2, y: 0, tile: Tile::Wall }, Segment::Tile { x: 0, y: 1, tile: Tile::Ball }, Segment::Score(7)] {
+            screen.draw(segment);
+        }
+        assert_eq!((screen.width(), screen.height()), (3, 2));
+        assert_eq!(screen.find(Tile::Ball), Some((0, 1)));
+        assert_eq!((screen.ball(), screen.paddle()), (Some((0, 1)), None));
+        assert_eq!(screen.render(), "  █\n●  \nScore: 7");
+
+        let frame = screen.end_frame();
+        assert_eq!(frame, Frame { number: 0, changed: vec![(2, 0), (0, 1)], score_changed: true });
+        assert_eq!(screen.render_changes(&frame), "\x1b[1;3H█\x1b[2;1H●\x1b[3;1HScore: 7\x1b[K");
+
+        // Drawing the same tile doesn't change the cell.
+        screen.draw(Segment::Tile { x: 2, y: 0, tile: Tile::Wall });
+        screen.draw(Segment::Score(7));
+        assert_eq!(screen.end_frame(), Frame { number: 1, changed: vec![], score_changed: false });
+    }
+
+    #[test]
+    fn arcade_frames() {
+        // Draw a ball at (1, 0), read the joystick, move the ball to its new x position then show the score.
+        let c = [104,1,104,0,104,4,3,100,104,1,104,0,104,0,4,100,104,0,104,4,104,-1,104,0,104,10,99];
+        let mut frames = Vec::new();
+        let controller = |_: &Screen, frame: &Frame| { frames.push(frame.clone()); Input::Value(2) };
+        let mut arcade = Framed::new(Arcade::new(controller), FRAMING);
+        assert_eq!(Machine::new(&c).run(&mut arcade), Ok(Status::Halted));
+
+        let arcade = arcade.into_result().unwrap();
+        assert_eq!(arcade.screen.find(Tile::Ball), Some((2, 0)));
+        assert_eq!(arcade.screen.ball(), Some((2, 0)));
+        assert_eq!(arcade.screen.score(), Some(10));
+        assert_eq!(frames, vec![Frame { number: 0, changed: vec![(1, 0)], score_changed: false }]);
+    }
+}
@@ -0,0 +1,227 @@
+use std::{collections::BTreeSet, convert::TryFrom};
+
+use super::{Context, Error, IO, Input, Machine, Status};
+
+// Printable ASCII characters and new line.
+fn is_printable(value: i64) -> bool {
+    value == 10 || (32 ..= 126).contains(&value)
+}
+
+fn to_char(value: i64) -> char {
+    u8::try_from(value).map(char::from).unwrap_or('?')
+}
+
+// A run of printable characters stored in memory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Text {
+    pub address: usize, // Address of the first character.
+    pub text: String,
+    pub emitted_by: BTreeSet<usize>, // Addresses of the output instructions which have written a character of the text.
+}
+
+impl Text {
+    fn contains(&self, address: usize) -> bool {
+        address >= self.address && address < self.address + self.text.len()
+    }
+}
+
+// Return the runs of at least 'min_len' printable characters, without running the program.
+pub fn scan(memory: &[i64], min_len: usize) -> Vec<Text> {
+    let mut texts = Vec::new();
+    let mut start = 0;
+    for address in 0 ..= memory.len() {
+        if address < memory.len() && is_printable(memory[address]) {
+            continue
+        }
+        if address - start >= min_len.max(1) {
+            texts.push(Text { address: start, text: memory[start .. address].iter().map(|value| to_char(*value)).collect(), emitted_by: BTreeSet::new() });
+        }
+        start = address + 1;
+    }
+    texts
+}
+
+// A value written by an output instruction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Output {
+    pub instruction: usize, // Address of the output instruction.
+    pub source: Option<usize>, // Address the value has been read from, 'None' in immediate mode.
+    pub value: i64,
+}
+
+struct Tracer<'a> {
+    io: &'a mut dyn IO,
+    instruction: usize,
+    source: Option<usize>,
+    outputs: &'a mut Vec<Output>,
+}
+
+impl IO for Tracer<'_> {
+    fn read(&mut self) -> Input { self.io.read() }
+
+    fn write(&mut self, value: i64) {
+        self.outputs.push(Output { instruction: self.instruction, source: self.source, value });
+        self.io.write(value)
+    }
+
+    fn halt(&self) -> bool { self.io.halt() }
+
+    fn finished(&mut self) { self.io.finished() }
+
+    fn read_with_context(&mut self, context: &Context) -> Input { self.io.read_with_context(context) }
+
+    fn write_with_context(&mut self, value: i64, context: &Context) {
+        self.outputs.push(Output { instruction: self.instruction, source: self.source, value });
+        self.io.write_with_context(value, context)
+    }
+}
+
+// Run the machine like 'Machine::run' and append each written value with the instruction which has written it.
+pub fn trace(machine: &mut Machine, io: &mut dyn IO, outputs: &mut Vec<Output>) -> Result<Status, Error> {
+    let mut tracer = Tracer { io, instruction: 0, source: None, outputs };
+    loop {
+        let memory = machine.memory();
+        let cursor = machine.cursor();
+        let parameter = memory.get(cursor + 1);
+        tracer.instruction = cursor;
+        tracer.source =
+            match memory.get(cursor) / 100 % 10 {
+                0 => usize::try_from(parameter).ok(),
+                2 => usize::try_from(parameter + machine.relative_base()).ok(),
+                _ => None
+            };
+        if let Some(status) = machine.step(&mut tracer)? {
+            return Ok(status)
+        }
+    }
+}
+
+// Add to each text the output instructions which have read one of its characters.
+pub fn link(texts: &mut [Text], outputs: &[Output]) {
+    for output in outputs.iter() {
+        if let Some(source) = output.source {
+            if let Some(text) = texts.iter_mut().find(|text| text.contains(source)) {
+                text.emitted_by.insert(output.instruction);
+            }
+        }
+    }
+}
+
+// Consecutive printable values written by a program, they may not be stored as is in memory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Message {
+    pub text: String,
+    pub instructions: BTreeSet<usize>, // Addresses of the output instructions which have written the message.
+}
+
+// Return the messages of at least 'min_len' characters.
+pub fn messages(outputs: &[Output], min_len: usize) -> Vec<Message> {
+    let mut messages = Vec::new();
+    let mut current = Message { text: String::new(), instructions: BTreeSet::new() };
+    for output in outputs.iter().map(Some).chain(std::iter::once(None)) {
+        match output {
+            Some(output) if is_printable(output.value) => {
+                current.text.push(to_char(output.value));
+                current.instructions.insert(output.instruction);
+            },
+            _ => {
+                if !current.text.is_empty() && current.text.len() >= min_len {
+                    messages.push(current);
+                }
+                current = Message { text: String::new(), instructions: BTreeSet::new() };
+            }
+        }
+    }
+    messages
+}
+
+fn to_hex(value: i64) -> String {
+    if value < 0 { format!("-{:x}", value.unsigned_abs()) } else { format!("{:x}", value) }
+}
+
+// A dump like 'hexdump -C': the address, 'width' values in hexadecimal and their printable characters.
+// Lines identical to the previous one are replaced by a single '*'.
+pub fn hexdump(memory: &[i64], width: usize) -> String {
+    let width = width.max(1);
+    let cell_width = memory.iter().map(|value| to_hex(*value).len()).max().unwrap_or(1);
+    let mut result = String::new();
+    let mut previous: Option<&[i64]> = None;
+    let mut skipping = false;
+
+    for (n, line) in memory.chunks(width).enumerate() {
+        if previous == Some(line) && line.len() == width {
+            if !skipping {
+                result += "*\n";
+                skipping = true;
+            }
+            continue
+        }
+        previous = Some(line);
+        skipping = false;
+
+        result += &format!("{:08x} ", n * width);
+        for i in 0 .. width {
+            match line.get(i) {
+                Some(value) => result += &format!(" {:>1$}", to_hex(*value), cell_width),
+                None => result += &" ".repeat(cell_width + 1)
+            }
+        }
+        let ascii: String = line.iter().map(|value| if *value != 10 && is_printable(*value) { to_char(*value) } else { '.' }).collect();
+        result += &format!("  |{}|\n", ascii);
+    }
+    result += &format!("{:08x}\n", memory.len());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Buffer;
+
+    // Output "Hi\n" stored at 9, 10 and 11 then '!' in immediate mode.
+    const HI: [i64; 12] = [4,9,4,10,4,11,104,33,99,72,105,10];
+
+    #[test]
+    fn scan_memory() {
+        let texts = scan(&HI, 3);
+        assert_eq!(texts, vec![Text { address: 6, text: String::from("h!cHi\n"), emitted_by: BTreeSet::new() }]);
+        assert_eq!(scan(&HI, 1).len(), 2);
+        assert_eq!(scan(&[], 1), vec![]);
+    }
+
+    #[test]
+    fn trace_and_link() {
+        let mut outputs = Vec::new();
+        assert_eq!(trace(&mut Machine::new(&HI), &mut Buffer::new(), &mut outputs), Ok(Status::Halted));
+        assert_eq!(outputs[0], Output { instruction: 0, source: Some(9), value: 72 });
+        assert_eq!(outputs[3], Output { instruction: 6, source: None, value: 33 });
+
+        let mut texts = scan(&HI, 3);
+        link(&mut texts, &outputs);
+        assert_eq!(texts[0].emitted_by, [0, 2, 4].iter().copied().collect());
+
+        let messages = messages(&outputs, 1);
+        assert_eq!(messages, vec![Message { text: String::from("Hi\n!"), instructions: [0, 2, 4, 6].iter().copied().collect() }]);
+    }
+
+    #[test]
+    fn relative_source() {
+        // Output the value at 'relative_base + 1' with the relative base set to 6.
+        let mut outputs = Vec::new();
+        trace(&mut Machine::new(&[109,6,204,1,99,0,0,42]), &mut Buffer::new(), &mut outputs).unwrap();
+        assert_eq!(outputs, vec![Output { instruction: 2, source: Some(7), value: 42 }]);
+    }
+
+    #[test]
+    fn dump() {
+        let memory = [72, 105, -1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 255];
+        assert_eq!(
+            hexdump(&memory, 4),
+            "00000000  48 69 -1  0  |Hi..|\n\
+             00000004   0  0  0  0  |....|\n\
+             *\n\
+             0000000c   0 ff        |..|\n\
+             0000000e\n"
+        );
+    }
+}
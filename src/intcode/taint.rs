@@ -0,0 +1,220 @@
+use std::{collections::{BTreeMap, BTreeSet, HashMap}, convert::TryFrom, fmt};
+
+use super::{Context, Error, IO, Input, Machine, Mode, Status, read_op_and_modes};
+
+// The label of an input is its index among the values read by the program.
+pub type Labels = BTreeSet<usize>;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    LessThan,
+    Equals,
+    JumpIfTrue,
+    JumpIfFalse,
+}
+
+// A comparison or a conditional jump whose operands depend on some inputs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Decision {
+    pub kind: Kind,
+    pub labels: Labels, // Union of the labels of all its tainted executions.
+    pub executions: u64, // Number of executions with at least one tainted operand.
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    pub inputs: Vec<i64>, // Values read by the program, in order.
+    pub decisions: BTreeMap<usize, Decision>, // By address of the instruction.
+}
+
+impl Report {
+    pub fn new() -> Self { Report { inputs: Vec::new(), decisions: BTreeMap::new() } }
+
+    // Inputs influencing at least one decision.
+    pub fn influential_inputs(&self) -> Labels {
+        self.decisions.values().flat_map(|decision| decision.labels.iter().copied()).collect()
+    }
+
+    // Addresses of the decisions depending on the given input.
+    pub fn decisions_depending_on(&self, label: usize) -> Vec<usize> {
+        self.decisions.iter().filter(|(_, decision)| decision.labels.contains(&label)).map(|(address, _)| *address).collect()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (label, value) in self.inputs.iter().enumerate() {
+            writeln!(f, "input {}: {} -> decisions {:?}", label, value, self.decisions_depending_on(label))?;
+        }
+        for (address, decision) in self.decisions.iter() {
+            writeln!(f, "{}: {:?} depends on inputs {:?} ({} executions)", address, decision.kind, decision.labels, decision.executions)?;
+        }
+        Ok(())
+    }
+}
+
+struct Tracker<'a> {
+    io: &'a mut dyn IO,
+    report: &'a mut Report,
+}
+
+impl Tracker<'_> {
+    fn label(&mut self, input: Input) -> Input {
+        if let Input::Value(value) = input {
+            self.report.inputs.push(value);
+        }
+        input
+    }
+}
+
+impl IO for Tracker<'_> {
+    fn read(&mut self) -> Input {
+        let input = self.io.read();
+        self.label(input)
+    }
+
+    fn write(&mut self, value: i64) { self.io.write(value) }
+
+    fn halt(&self) -> bool { self.io.halt() }
+
+    fn finished(&mut self) { self.io.finished() }
+
+    fn read_with_context(&mut self, context: &Context) -> Input {
+        let input = self.io.read_with_context(context);
+        self.label(input)
+    }
+
+    fn write_with_context(&mut self, value: i64, context: &Context) { self.io.write_with_context(value, context) }
+}
+
+// Address of a parameter, 'None' in immediate mode.
+fn address(machine: &Machine, position: usize, mode: Mode) -> Option<usize> {
+    let value = machine.memory().get(position);
+    match mode {
+        Mode::Position => usize::try_from(value).ok(),
+        Mode::Immediate => None,
+        Mode::Relative => usize::try_from(value + machine.relative_base()).ok()
+    }
+}
+
+// Labels of the values stored in memory. Only the explicit data flow is tracked: a value read
+// at a tainted address (including a tainted relative base) or written after a tainted jump isn't tainted.
+struct Shadow {
+    memory: HashMap<usize, Labels>,
+}
+
+impl Shadow {
+    fn labels(&self, machine: &Machine, position: usize, mode: Mode) -> Labels {
+        address(machine, position, mode).and_then(|address| self.memory.get(&address)).cloned().unwrap_or_default()
+    }
+
+    fn set(&mut self, address: Option<usize>, labels: Labels) {
+        if let Some(address) = address {
+            if labels.is_empty() {
+                self.memory.remove(&address);
+            } else {
+                self.memory.insert(address, labels);
+            }
+        }
+    }
+}
+
+// Run the machine like 'Machine::run', each value read is labeled and its label propagated through
+// the arithmetic, the comparisons and the memory. The decisions depending on inputs are added to the report.
+pub fn track(machine: &mut Machine, io: &mut dyn IO, report: &mut Report) -> Result<Status, Error> {
+    let mut shadow = Shadow { memory: HashMap::new() };
+    let mut tracker = Tracker { io, report };
+    loop {
+        let cursor = machine.cursor();
+        let (op, modes) = read_op_and_modes(machine.memory().get(cursor));
+        let operands = |shadow: &Shadow, n: usize| -> Labels {
+            (0 .. n).flat_map(|i| shadow.labels(machine, cursor + 1 + i, modes[i])).collect()
+        };
+
+        let mut decision = None;
+        let mut written = None;
+        match op {
+            1 | 2 | 7 | 8 => {
+                let labels = operands(&shadow, 2);
+                if op == 7 || op == 8 {
+                    decision = Some((if op == 7 { Kind::LessThan } else { Kind::Equals }, labels.clone()));
+                }
+                written = Some((address(machine, cursor + 3, modes[2]), labels));
+            },
+            3 => written = Some((address(machine, cursor + 1, modes[0]), Labels::new())),
+            5 | 6 => decision = Some((if op == 5 { Kind::JumpIfTrue } else { Kind::JumpIfFalse }, operands(&shadow, 2))),
+            _ => ()
+        }
+
+        let nb_inputs = tracker.report.inputs.len();
+        let status = machine.step(&mut tracker)?;
+
+        // The instruction has no effect if the program is waiting for an input or has halted.
+        if status.is_none() {
+            if let Some((address, mut labels)) = written {
+                if op == 3 {
+                    labels.insert(nb_inputs);
+                }
+                shadow.set(address, labels);
+            }
+            if let Some((kind, labels)) = decision.filter(|(_, labels)| !labels.is_empty()) {
+                let entry = tracker.report.decisions.entry(cursor).or_insert(Decision { kind, labels: Labels::new(), executions: 0 });
+                entry.labels.extend(labels);
+                entry.executions += 1;
+            }
+        }
+
+        if let Some(status) = status {
+            return Ok(status)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Buffer;
+
+    fn track_program(code: &[i64], input: &[i64]) -> Report {
+        let mut report = Report::new();
+        assert_eq!(track(&mut Machine::new(code), &mut Buffer::from(input), &mut report), Ok(Status::Halted));
+        report
+    }
+
+    #[test]
+    fn only_first_input_matters() {
+        // Output the second input if the first one is less than 5.
+        let c = [3,100,3,101,1007,100,5,102,1006,102,13,4,101,99];
+        let report = track_program(&c, &[3, 42]);
+        assert_eq!(report.inputs, vec![3, 42]);
+        assert_eq!(report.decisions.keys().copied().collect::<Vec<usize>>(), vec![4, 8]);
+        assert_eq!(report.decisions[&4], Decision { kind: Kind::LessThan, labels: [0].iter().copied().collect(), executions: 1 });
+        assert_eq!(report.decisions[&8].kind, Kind::JumpIfFalse);
+        assert_eq!(report.influential_inputs(), [0].iter().copied().collect());
+        assert_eq!(report.decisions_depending_on(1), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn propagation_through_arithmetic() {
+        // Compare the sum of two inputs with 10, the first comparison only uses constants.
+        let c = [1107,1,2,104,3,100,3,101,1,100,101,102,1008,102,10,103,1005,103,21,104,0,99];
+        let report = track_program(&c, &[4, 6]);
+        assert_eq!(report.decisions.keys().copied().collect::<Vec<usize>>(), vec![12, 16]);
+        assert_eq!(report.decisions[&16].labels, [0, 1].iter().copied().collect());
+    }
+
+    #[test]
+    fn overwritten_value_loses_its_labels() {
+        // The input is overwritten by a constant before being compared.
+        let c = [3,100,1101,0,1,100,1008,100,1,101,99];
+        assert!(track_program(&c, &[7]).decisions.is_empty());
+    }
+
+    #[test]
+    fn relative_base() {
+        // Store the input at 'relative_base + 1' then compare it in relative mode.
+        let c = [109,50,203,1,21208,1,3,2,99];
+        let report = track_program(&c, &[3]);
+        assert_eq!(report.decisions[&4].labels, [0].iter().copied().collect());
+    }
+}
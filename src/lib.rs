@@ -0,0 +1,46 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+// The puzzle solutions are kept as they were first written, some of them predate a few Clippy lints.
+
+#[cfg(feature = "std")]
+pub mod common;
+pub mod intcode;
+#[cfg(feature = "std")]
+pub mod day01;
+#[cfg(feature = "std")]
+pub mod day02;
+#[cfg(feature = "std")]
+#[allow(clippy::iter_nth_zero, clippy::wildcard_in_or_patterns, clippy::manual_map)]
+pub mod day03;
+#[cfg(feature = "std")]
+pub mod day04;
+#[cfg(feature = "std")]
+#[allow(clippy::needless_borrow)]
+pub mod day06;
+#[cfg(feature = "std")]
+pub mod day07;
+#[cfg(feature = "std")]
+pub mod day08;
+#[cfg(feature = "std")]
+#[allow(clippy::unnecessary_sort_by)]
+pub mod day10;
+#[cfg(feature = "std")]
+pub mod day11;
+#[cfg(feature = "std")]
+#[allow(clippy::ptr_arg)]
+pub mod day12;
+#[cfg(feature = "std")]
+pub mod day13;
+#[cfg(feature = "std")]
+pub mod day14;
+#[cfg(feature = "std")]
+pub mod day15;
+#[cfg(feature = "std")]
+pub mod day16;
+#[cfg(feature = "std")]
+pub mod day17;
+#[cfg(feature = "std")]
+#[allow(clippy::redundant_closure, clippy::needless_borrow)]
+pub mod day18;
@@ -2,24 +2,7 @@ use std::env;
 use std::fs;
 use std::time::Instant;
 
-mod common;
-mod intcode;
-mod day01;
-mod day02;
-mod day03;
-mod day04;
-mod day06;
-mod day07;
-mod day08;
-mod day10;
-mod day11;
-mod day12;
-mod day13;
-mod day14;
-mod day15;
-mod day16;
-mod day17;
-mod day18;
+use advent_of_code_2019::*;
 
 fn day01() -> String {
     let masses = common::read_list_of_numbers("data/day01.input", "\n");
@@ -28,7 +11,7 @@ fn day01() -> String {
 
 fn day02() -> String {
     let code = common::read_list_of_numbers("data/day02.input", ",");
-    format!("part1: {}, part2: {}", day02::execute_op_code_with_state_fixed(&mut Vec::from(&code[..])), day02::find_noun_and_verb(&code))
+    format!("part1: {}, part2: {}", day02::execute_op_code_with_state_fixed(&code), day02::find_noun_and_verb(&code, 19_690_720, 0 ..= 99).map(|(noun, verb)| 100 * noun + verb).unwrap())
 }
 
 fn day03() -> String {
@@ -36,8 +19,8 @@ fn day03() -> String {
     let movements: Vec<&str> = file_content.lines().collect();
     format!(
         "part1: {}, part2: {}",
-        day03::manhattan_distance_from_cross_to_port(&day03::split_movements(&movements[0]), &day03::split_movements(&movements[1])),
-        day03::first_cross_sum_of_lengths(&day03::split_movements(&movements[0]), &day03::split_movements(&movements[1]))
+        day03::manhattan_distance_from_cross_to_port(&day03::split_movements(movements[0]), &day03::split_movements(movements[1])),
+        day03::first_cross_sum_of_lengths(&day03::split_movements(movements[0]), &day03::split_movements(movements[1]))
     )
 }
 
@@ -62,19 +45,20 @@ fn day06() -> String {
 fn day07() -> String {
     let code = common::read_list_of_numbers("data/day07.input", ",");
 
-    format!("part1: {}, part2: {}", day07::find_largest_last_thruster_signal(&code), day07::find_largest_last_thruster_signal_with_feedback_loop(&code))
+    let pool = threadpool::ThreadPool::new(4);
+    format!("part1: {}, part2: {}", day07::find_largest_last_thruster_signal(&code, Some(&pool)), day07::find_largest_last_thruster_signal_with_feedback_loop(&code, Some(&pool)))
 }
 
-fn day08() -> String {
-    let img = fs::read_to_string("data/day08.input").unwrap();
-
-    let raw = day08::read_from_string(&img);
-    let layers = day08::decode_image(&raw, 25, 6);
+// The text drawn in a layer or the reason why it can't be read.
+fn read_letters(layer: &[u8], width: usize) -> String {
+    common::ocr::recognize(layer, width, &common::ocr::SMALL).map_or_else(|error| error.to_string(), |recognized| recognized.text)
+}
 
-    let layer = day08::layer_with_fewer_0(&layers[..]);
-    let merged = day08::merge_layers(&layers[..]);
+fn day08() -> String {
+    let image = day08::sif::Image::decode(&fs::read_to_string("data/day08.input").unwrap(), 25, 6).unwrap();
+    let merged = image.flatten(day08::sif::TRANSPARENT);
 
-    format!("part1: {}, part2:\n{}", day08::one_digits_times_two_digits(layer), common::layer_to_printable_string(&merged, 25))
+    format!("part1: {}, part2: {}\n{}", image.checksum(), read_letters(&merged, 25), common::layer_to_printable_string(&merged, 25))
 }
 
 fn day09() -> String {
@@ -93,9 +77,9 @@ fn day10() -> String {
 fn day11() -> String {
     let code = common::read_list_of_numbers::<&str, i64>("data/day11.input", ",");
     let panels = day11::run_robot(&code, 1);
-    let (layer, width) = day11::panels_to_layer(&panels);
+    let (layer, width) = day11::panels_to_layer(&panels).unwrap();
 
-    format!("part1: {:?}, part2:\n{}", day11::run_robot(&code, 0).len(), common::layer_to_printable_string(&layer, width))
+    format!("part1: {:?}, part2: {}\n{}", day11::run_robot(&code, 0).len(), read_letters(&layer, width), common::layer_to_printable_string(&layer, width))
 }
 
 fn day12() -> String {
@@ -105,9 +89,7 @@ fn day12() -> String {
 
 fn day13() -> String {
     let code = common::read_list_of_numbers::<&str, i64>("data/day13.input", ",");
-    let mut modified_code = Vec::from(&code[..]);
-    modified_code[0] = 2;
-    format!("part1: {}, part2: {}", day13::count_nb_block(&code), day13::final_score(&modified_code))
+    format!("part1: {}, part2: {}", day13::count_nb_block(&code), day13::final_score(&day13::insert_quarters().apply(&code)))
 }
 
 fn day14() -> String {
@@ -132,10 +114,9 @@ fn day16() -> String {
 }
 
 fn day17() -> String {
-    let mut code = common::read_list_of_numbers("data/day17.input", ",");
+    let code = common::read_list_of_numbers("data/day17.input", ",");
     let intersections = day17::scaffold_intersections(&code);
-    code[0] = 2;
-    let dust = day17::collected_dust(&code);
+    let dust = day17::collected_dust(&day17::wake_up_robot().apply(&code));
     format!("part1: {}, part2: {}", intersections, dust)
 }
 